@@ -0,0 +1,18 @@
+use std::env;
+
+/// Flags the bundled SQLite must be compiled with for the `session` feature.
+const SESSION_FLAGS: &[&str] = &["-DSQLITE_ENABLE_SESSION", "-DSQLITE_ENABLE_PREUPDATE_HOOK"];
+
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(sqlite_session)");
+    println!("cargo::rerun-if-env-changed=LIBSQLITE3_FLAGS");
+
+    // libsqlite3-sys reads the same variable to compile the bundled SQLite
+    let flags = env::var("LIBSQLITE3_FLAGS").unwrap_or_default();
+    if SESSION_FLAGS
+        .iter()
+        .all(|flag| flags.split_whitespace().any(|f| f == *flag))
+    {
+        println!("cargo::rustc-cfg=sqlite_session");
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::ffi::{CString, c_int, c_void};
+use std::mem::size_of;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use libsqlite3_sys::{
+    sqlite3, sqlite3_aggregate_context, sqlite3_context, sqlite3_create_function_v2,
+    sqlite3_create_window_function, sqlite3_result_error, sqlite3_result_error_nomem,
+    sqlite3_user_data, sqlite3_value,
+};
+use rquickjs::{Ctx, Exception, FromJs, Function, JsLifetime, Object, Result, Value};
+
+use super::Argument;
+use super::callback::{Call, Caller, RawArgs, RawContext};
+use super::error::SqliteError;
+use super::function::{self, FunctionOptions};
+
+static NEXT_STATE: AtomicU64 = AtomicU64::new(1);
+
+/// The JS callbacks of an aggregate or window function.
+#[derive(JsLifetime)]
+pub struct Aggregate<'js> {
+    start: Value<'js>,
+    step: Function<'js>,
+    inverse: Option<Function<'js>>,
+    result: Option<Function<'js>>,
+}
+
+impl<'js> Aggregate<'js> {
+    pub fn from_options(ctx: &Ctx<'js>, options: Value<'js>) -> Result<(FunctionOptions, Self)> {
+        let obj = Object::from_js(ctx, options.clone())?;
+        let function_options = FunctionOptions::from_js(ctx, options)?;
+        let step = obj
+            .get::<_, Option<Function<'js>>>("step")?
+            .ok_or_else(|| Exception::throw_type(ctx, "The step option must be a function"))?;
+        let aggregate = Self {
+            start: obj.get("start")?,
+            step,
+            inverse: obj.get("inverse")?,
+            result: obj.get("result")?,
+        };
+        Ok((function_options, aggregate))
+    }
+
+    /// Number of SQL arguments, the first parameter of `step` is the accumulator.
+    pub fn arity(&self, ctx: &Ctx<'js>, options: &FunctionOptions) -> Result<c_int> {
+        options.arity(ctx, &self.step, 1)
+    }
+
+    pub fn is_window(&self) -> bool {
+        self.inverse.is_some()
+    }
+
+    fn start(&self) -> Result<Value<'js>> {
+        match self.start.as_function() {
+            Some(start) => start.call(()),
+            None if self.start.is_undefined() => Ok(Value::new_null(self.start.ctx().clone())),
+            None => Ok(self.start.clone()),
+        }
+    }
+}
+
+struct AggregateHandle {
+    id: usize,
+    caller: Caller,
+}
+
+/// Creates the installer of an aggregate function calling the JS callbacks `id`.
+/// Window functions are created when `window` is set.
+pub fn installer(
+    ctx: &Ctx<'_>,
+    name: String,
+    arity: c_int,
+    flags: c_int,
+    window: bool,
+    id: usize,
+    caller: Caller,
+) -> Result<impl Fn(NonNull<sqlite3>) -> std::result::Result<(), SqliteError> + Send + Sync + 'static>
+{
+    let name = CString::new(name)
+        .map_err(|_| Exception::throw_type(ctx, "Function name must not contain NUL characters"))?;
+    Ok(move |db: NonNull<sqlite3>| {
+        let handle = Box::into_raw(Box::new(AggregateHandle {
+            id,
+            caller: caller.clone(),
+        }));
+        // SAFETY: The connection handle is locked by the caller. SQLite owns
+        // the handle from now on and frees it with `destroy_aggregate`.
+        let code = unsafe {
+            if window {
+                sqlite3_create_window_function(
+                    db.as_ptr(),
+                    name.as_ptr(),
+                    arity,
+                    flags,
+                    handle as *mut c_void,
+                    Some(call_step),
+                    Some(call_final),
+                    Some(call_value),
+                    Some(call_inverse),
+                    Some(destroy_aggregate),
+                )
+            } else {
+                sqlite3_create_function_v2(
+                    db.as_ptr(),
+                    name.as_ptr(),
+                    arity,
+                    flags,
+                    handle as *mut c_void,
+                    None,
+                    Some(call_step),
+                    Some(call_final),
+                    Some(destroy_aggregate),
+                )
+            }
+        };
+        function::check(code)
+    })
+}
+
+/// Returns the key of the accumulator of an aggregate context, allocating it
+/// if `create` is set. Returns `None` if no row was aggregated yet.
+unsafe fn state(context: *mut sqlite3_context, create: bool) -> Option<u64> {
+    let size = if create { size_of::<u64>() as c_int } else { 0 };
+    // SAFETY: SQLite zeroes the aggregate context on allocation.
+    let state = unsafe { sqlite3_aggregate_context(context, size) } as *mut u64;
+    if state.is_null() {
+        return None;
+    }
+    unsafe {
+        if *state == 0 {
+            if !create {
+                return None;
+            }
+            *state = NEXT_STATE.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(*state)
+    }
+}
+
+unsafe fn sqlite_step(
+    context: *mut sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+    inverse: bool,
+) {
+    // SAFETY: The user data is the handle given when creating the function.
+    let handle = unsafe { &*(sqlite3_user_data(context) as *const AggregateHandle) };
+    let Some(state) = (unsafe { state(context, true) }) else {
+        unsafe { sqlite3_result_error_nomem(context) };
+        return;
+    };
+    let called = handle.caller.call(|done| Call::Step {
+        id: handle.id,
+        state,
+        inverse,
+        context: RawContext(context),
+        args: RawArgs(argv, argc),
+        done,
+    });
+    if called.is_none() {
+        unsafe { sqlite3_result_error(context, c"Database is closed".as_ptr(), -1) };
+    }
+}
+
+unsafe fn sqlite_finish(context: *mut sqlite3_context, finish: bool) {
+    // SAFETY: The user data is the handle given when creating the function.
+    let handle = unsafe { &*(sqlite3_user_data(context) as *const AggregateHandle) };
+    let state = unsafe { state(context, false) };
+    let called = handle.caller.call(|done| Call::Result {
+        id: handle.id,
+        state,
+        finish,
+        context: RawContext(context),
+        done,
+    });
+    if called.is_none() {
+        unsafe { sqlite3_result_error(context, c"Database is closed".as_ptr(), -1) };
+    }
+}
+
+unsafe extern "C" fn call_step(
+    context: *mut sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) {
+    unsafe { sqlite_step(context, argc, argv, false) }
+}
+
+unsafe extern "C" fn call_inverse(
+    context: *mut sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) {
+    unsafe { sqlite_step(context, argc, argv, true) }
+}
+
+unsafe extern "C" fn call_value(context: *mut sqlite3_context) {
+    unsafe { sqlite_finish(context, false) }
+}
+
+unsafe extern "C" fn call_final(context: *mut sqlite3_context) {
+    unsafe { sqlite_finish(context, true) }
+}
+
+unsafe extern "C" fn destroy_aggregate(data: *mut c_void) {
+    drop(unsafe { Box::from_raw(data as *mut AggregateHandle) });
+}
+
+/// Calls the `step` (or `inverse`) callback of an aggregate on the JS context.
+pub fn step<'js>(
+    ctx: &Ctx<'js>,
+    aggregate: Option<&Aggregate<'js>>,
+    accumulators: &mut HashMap<u64, Value<'js>>,
+    state: u64,
+    inverse: bool,
+    context: &RawContext,
+    args: &RawArgs,
+) {
+    let result = (|| {
+        let aggregate =
+            aggregate.ok_or_else(|| Exception::throw_message(ctx, "Unknown function"))?;
+        let callback = match (inverse, &aggregate.inverse) {
+            (false, _) => &aggregate.step,
+            (true, Some(inverse)) => inverse,
+            (true, None) => {
+                return Err(Exception::throw_message(
+                    ctx,
+                    "Aggregate has no inverse function",
+                ));
+            }
+        };
+        let accumulator = match accumulators.remove(&state) {
+            Some(accumulator) => accumulator,
+            None => aggregate.start()?,
+        };
+        let mut values = function::read_args(ctx, args)?;
+        values.0.insert(0, accumulator.clone());
+        let value = callback.call::<_, Value>((values,))?;
+        // Returning undefined keeps the accumulator, which allows mutating it in place
+        let accumulator = if value.is_undefined() {
+            accumulator
+        } else {
+            value
+        };
+        accumulators.insert(state, accumulator);
+        Ok(())
+    })();
+    if let Err(err) = result {
+        function::set_error(ctx, context, err);
+    }
+}
+
+/// Computes the current (or final) result of an aggregate on the JS context.
+pub fn result<'js>(
+    ctx: &Ctx<'js>,
+    aggregate: Option<&Aggregate<'js>>,
+    accumulators: &mut HashMap<u64, Value<'js>>,
+    state: Option<u64>,
+    finish: bool,
+    context: &RawContext,
+) {
+    let accumulator = match state {
+        Some(state) if finish => accumulators.remove(&state),
+        Some(state) => accumulators.get(&state).cloned(),
+        None => None,
+    };
+    let result = (|| {
+        let aggregate =
+            aggregate.ok_or_else(|| Exception::throw_message(ctx, "Unknown function"))?;
+        let accumulator = match accumulator {
+            Some(accumulator) => accumulator,
+            None => aggregate.start()?,
+        };
+        let value = match &aggregate.result {
+            Some(result) => result.call::<_, Value>((accumulator,))?,
+            None => accumulator,
+        };
+        let value = Argument::from_js(ctx, value)?;
+        // SAFETY: The worker thread is blocked until the call is answered.
+        unsafe { value.set_result(context.0) };
+        Ok(())
+    })();
+    if let Err(err) = result {
+        function::set_error(ctx, context, err);
+    }
+}
@@ -1,10 +1,21 @@
-use rquickjs::{Ctx, Exception, FromJs, Result, TypedArray};
+use std::ffi::{c_int, c_void};
+
+use libsqlite3_sys::{
+    SQLITE_TRANSIENT, sqlite3_bind_blob, sqlite3_bind_double, sqlite3_bind_int64,
+    sqlite3_bind_null, sqlite3_bind_text, sqlite3_context, sqlite3_result_blob,
+    sqlite3_result_double, sqlite3_result_int64, sqlite3_result_null, sqlite3_result_text,
+    sqlite3_stmt,
+};
+use rquickjs::function::Constructor;
+use rquickjs::{Ctx, Exception, FromJs, Function, Result, TypedArray};
 use rquickjs_extra_utils::ffi::{CString, CVec};
 use rquickjs_extra_utils::result::ResultExt;
 use sqlx::Sqlite;
 use sqlx::query::Query;
 use sqlx::sqlite::SqliteArguments;
 
+use super::parameters::is_plain_object;
+
 #[derive(Debug)]
 pub enum Argument<'js> {
     Null,
@@ -18,6 +29,8 @@ impl<'js> FromJs<'js> for Argument<'js> {
     fn from_js(ctx: &Ctx<'js>, value: rquickjs::Value<'js>) -> Result<Self> {
         if value.is_undefined() || value.is_null() {
             return Ok(Argument::Null);
+        } else if let Some(bool) = value.as_bool() {
+            return Ok(Argument::Integer(bool as i64));
         } else if let Some(int) = value.as_int() {
             return Ok(Argument::Integer(int as i64));
         } else if let Some(big_int) = value.as_big_int() {
@@ -33,6 +46,17 @@ impl<'js> FromJs<'js> for Argument<'js> {
             return Ok(Argument::Blob(CVec::from_array(
                 TypedArray::<u8>::from_value(value.clone()).or_throw(ctx)?,
             )?));
+        } else if let Some(object) = value.as_object()
+            && object.is_instance_of(ctx.globals().get::<_, Constructor>("Date")?)
+        {
+            let iso = object
+                .get::<_, Function>("toISOString")?
+                .call((rquickjs::function::This(object.clone()),))?;
+            return Ok(Argument::Text(CString::from_string(iso)?));
+        } else if (value.is_array() || is_plain_object(ctx, &value)?)
+            && let Some(json) = ctx.json_stringify(value.clone())?
+        {
+            return Ok(Argument::Text(CString::from_string(json)?));
         }
         Err(Exception::throw_type(
             ctx,
@@ -58,4 +82,60 @@ impl<'js> Argument<'js> {
             Argument::Blob(blob) => query.try_bind(blob.as_slice()).or_throw(ctx),
         }
     }
+
+    /// Sets the value as the result of an application-defined function.
+    /// The value is copied by SQLite.
+    ///
+    /// # Safety
+    /// `context` must be a valid `sqlite3_context` that is not used concurrently.
+    pub unsafe fn set_result(&self, context: *mut sqlite3_context) {
+        unsafe {
+            match self {
+                Argument::Null => sqlite3_result_null(context),
+                Argument::Integer(int) => sqlite3_result_int64(context, *int),
+                Argument::Real(float) => sqlite3_result_double(context, *float),
+                Argument::Text(string) => sqlite3_result_text(
+                    context,
+                    string.as_ptr(),
+                    string.len() as c_int,
+                    SQLITE_TRANSIENT(),
+                ),
+                Argument::Blob(blob) => sqlite3_result_blob(
+                    context,
+                    blob.as_ptr() as *const c_void,
+                    blob.len() as c_int,
+                    SQLITE_TRANSIENT(),
+                ),
+            }
+        }
+    }
+
+    /// Binds the value to the parameter `index` of a raw statement.
+    /// The value is copied by SQLite.
+    ///
+    /// # Safety
+    /// `stmt` must be a valid `sqlite3_stmt` that is not used concurrently.
+    pub unsafe fn bind_raw(&self, stmt: *mut sqlite3_stmt, index: c_int) -> c_int {
+        unsafe {
+            match self {
+                Argument::Null => sqlite3_bind_null(stmt, index),
+                Argument::Integer(int) => sqlite3_bind_int64(stmt, index, *int),
+                Argument::Real(float) => sqlite3_bind_double(stmt, index, *float),
+                Argument::Text(string) => sqlite3_bind_text(
+                    stmt,
+                    index,
+                    string.as_ptr(),
+                    string.len() as c_int,
+                    SQLITE_TRANSIENT(),
+                ),
+                Argument::Blob(blob) => sqlite3_bind_blob(
+                    stmt,
+                    index,
+                    blob.as_ptr() as *const c_void,
+                    blob.len() as c_int,
+                    SQLITE_TRANSIENT(),
+                ),
+            }
+        }
+    }
 }
@@ -0,0 +1,201 @@
+use std::ffi::{CString, c_int};
+use std::ptr::NonNull;
+use std::time::{Duration, Instant};
+
+use libsqlite3_sys::{
+    SQLITE_BUSY, SQLITE_DONE, SQLITE_LOCKED, SQLITE_OK, SQLITE_OPEN_CREATE, SQLITE_OPEN_READWRITE,
+    SQLITE_READONLY, sqlite3, sqlite3_backup, sqlite3_backup_finish, sqlite3_backup_init,
+    sqlite3_backup_pagecount, sqlite3_backup_remaining, sqlite3_backup_step, sqlite3_busy_timeout,
+    sqlite3_db_readonly,
+};
+use rquickjs::{Ctx, Exception, FromJs, Function, Object, Result, Value};
+use sqlx::{SqliteConnection, SqlitePool};
+
+use super::error::{ResultExt as _, SqliteError};
+use super::raw::RawConnection;
+
+/// Time to wait before retrying a step that found a database locked.
+const RETRY_DELAY: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone)]
+pub struct BackupOptions<'js> {
+    pub pages_per_step: c_int,
+    pub progress: Option<Function<'js>>,
+}
+
+impl Default for BackupOptions<'_> {
+    fn default() -> Self {
+        Self {
+            pages_per_step: 100,
+            progress: None,
+        }
+    }
+}
+
+impl<'js> FromJs<'js> for BackupOptions<'js> {
+    fn from_js(ctx: &Ctx<'js>, value: Value<'js>) -> Result<Self> {
+        let default = BackupOptions::default();
+        let obj = value.get::<Object<'js>>()?;
+        let pages_per_step = obj
+            .get::<_, Option<c_int>>("pagesPerStep")?
+            .unwrap_or(default.pages_per_step);
+        if pages_per_step == 0 {
+            return Err(Exception::throw_range(
+                ctx,
+                "pagesPerStep must be a positive number, or negative to copy all pages at once",
+            ));
+        }
+        let progress = obj.get::<_, Option<Function<'js>>>("progress")?;
+        Ok(Self {
+            pages_per_step,
+            progress,
+        })
+    }
+}
+
+/// An online backup, finished on drop.
+struct Backup(NonNull<sqlite3_backup>);
+
+impl Backup {
+    /// Starts a backup of the main database of `source` to the main database of `dest`.
+    ///
+    /// # Safety
+    /// Both connections must be valid and not used concurrently.
+    unsafe fn init(dest: NonNull<sqlite3>, source: NonNull<sqlite3>) -> Option<Self> {
+        let backup = unsafe {
+            sqlite3_backup_init(
+                dest.as_ptr(),
+                c"main".as_ptr(),
+                source.as_ptr(),
+                c"main".as_ptr(),
+            )
+        };
+        NonNull::new(backup).map(Self)
+    }
+}
+
+impl Drop for Backup {
+    fn drop(&mut self) {
+        // SAFETY: The backup is finished once.
+        unsafe { sqlite3_backup_finish(self.0.as_ptr()) };
+    }
+}
+
+/// Copies the main database of the pool to `destination` with the online backup API.
+///
+/// The source connection is only locked while a step copies pages, so other
+/// connections can write between steps. Read-only databases are refused, as
+/// the backup creates or overwrites a file.
+pub async fn backup<'js>(
+    ctx: &Ctx<'js>,
+    pool: &SqlitePool,
+    destination: String,
+    options: BackupOptions<'js>,
+) -> Result<Object<'js>> {
+    let path = CString::new(destination)
+        .map_err(|_| Exception::throw_type(ctx, "Path must not contain NUL characters"))?;
+    let mut conn = pool.acquire().await.or_throw_sqlite(ctx)?;
+    {
+        let mut handle = conn.lock_handle().await.or_throw_sqlite(ctx)?;
+        // SAFETY: The source handle is locked.
+        let read_only =
+            unsafe { sqlite3_db_readonly(handle.as_raw_handle().as_ptr(), c"main".as_ptr()) };
+        if read_only == 1 {
+            return Err(SqliteError::new(
+                SQLITE_READONLY,
+                "Read-only databases cannot be backed up",
+            )
+            .throw(ctx));
+        }
+    }
+    let busy_timeout: c_int = sqlx::query_scalar("PRAGMA busy_timeout")
+        .fetch_one(&mut *conn)
+        .await
+        .or_throw_sqlite(ctx)?;
+    let dest = RawConnection::open(&path, SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE)
+        .map_err(|err| err.context("Unable to open backup destination").throw(ctx))?;
+    // SAFETY: The destination connection is owned by this backup.
+    unsafe { sqlite3_busy_timeout(dest.as_raw_handle().as_ptr(), busy_timeout) };
+
+    let backup = {
+        let mut handle = conn.lock_handle().await.or_throw_sqlite(ctx)?;
+        // SAFETY: The source handle is locked.
+        unsafe { Backup::init(dest.as_raw_handle(), handle.as_raw_handle()) }.ok_or_else(|| {
+            SqliteError::from_handle(dest.as_raw_handle())
+                .context("Unable to start backup")
+                .throw(ctx)
+        })?
+    };
+
+    let busy_timeout = Duration::from_millis(busy_timeout.max(0) as u64);
+    let result = steps(ctx, &mut conn, &backup, &options, busy_timeout).await;
+    // The source handle must also be locked while the backup is finished
+    let _handle = conn.lock_handle().await.or_throw_sqlite(ctx)?;
+    drop(backup);
+    result
+}
+
+async fn steps<'js>(
+    ctx: &Ctx<'js>,
+    conn: &mut SqliteConnection,
+    backup: &Backup,
+    options: &BackupOptions<'js>,
+    busy_timeout: Duration,
+) -> Result<Object<'js>> {
+    let mut locked_since = None;
+    loop {
+        let code = {
+            let _handle = conn.lock_handle().await.or_throw_sqlite(ctx)?;
+            // SAFETY: The source handle is locked while pages are copied.
+            unsafe { sqlite3_backup_step(backup.0.as_ptr(), options.pages_per_step) }
+        };
+        if code == SQLITE_BUSY || code == SQLITE_LOCKED {
+            // Another connection holds a lock, the step is retried until the busy timeout
+            let since = *locked_since.get_or_insert_with(Instant::now);
+            if since.elapsed() < busy_timeout {
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        }
+        locked_since = None;
+        if code != SQLITE_OK && code != SQLITE_DONE {
+            return Err(SqliteError::from_code(code)
+                .context("Backup failed")
+                .throw(ctx));
+        }
+
+        let status = Object::new(ctx.clone())?;
+        // SAFETY: The counts are updated by the last step.
+        unsafe {
+            status.set("totalPages", sqlite3_backup_pagecount(backup.0.as_ptr()))?;
+            status.set(
+                "remainingPages",
+                sqlite3_backup_remaining(backup.0.as_ptr()),
+            )?;
+        }
+        if code == SQLITE_DONE {
+            return Ok(status);
+        }
+        if let Some(progress) = &options.progress {
+            progress.call::<_, ()>((status,))?;
+        }
+    }
+}
+
+/// Replaces the main database of `dest` with the one of `source` in a single step.
+///
+/// # Safety
+/// Both connections must be valid and not used concurrently.
+pub unsafe fn copy(
+    dest: NonNull<sqlite3>,
+    source: NonNull<sqlite3>,
+) -> std::result::Result<(), SqliteError> {
+    let backup =
+        unsafe { Backup::init(dest, source) }.ok_or_else(|| SqliteError::from_handle(dest))?;
+    let code = unsafe { sqlite3_backup_step(backup.0.as_ptr(), -1) };
+    drop(backup);
+    if code != SQLITE_DONE {
+        return Err(SqliteError::from_code(code));
+    }
+    Ok(())
+}
@@ -0,0 +1,378 @@
+use std::ffi::{CString, c_int, c_void};
+use std::ptr::{self, NonNull};
+use std::sync::Arc;
+
+use libsqlite3_sys::{
+    SQLITE_NOMEM, SQLITE_OK, sqlite3_blob, sqlite3_blob_bytes, sqlite3_blob_close,
+    sqlite3_blob_open, sqlite3_blob_read, sqlite3_blob_write,
+};
+use rquickjs::function::Opt;
+use rquickjs::{
+    Ctx, Exception, FromJs, JsLifetime, Object, Result, TypedArray, Value, class::Trace,
+};
+use sqlx::pool::PoolConnection;
+use sqlx::{Sqlite, SqlitePool};
+use tokio::sync::Mutex;
+
+use super::error::{ResultExt as _, SqliteError};
+
+#[derive(Debug, Clone, Default)]
+pub struct BlobOptions {
+    pub read_only: bool,
+    pub database: Option<String>,
+}
+
+impl<'js> FromJs<'js> for BlobOptions {
+    fn from_js(_ctx: &Ctx<'js>, value: Value<'js>) -> Result<Self> {
+        let default = BlobOptions::default();
+        let obj = value.get::<Object<'js>>()?;
+        Ok(Self {
+            read_only: obj
+                .get::<_, Option<bool>>("readOnly")?
+                .unwrap_or(default.read_only),
+            database: obj.get("database")?,
+        })
+    }
+}
+
+/// A blob handle, closed on drop.
+struct RawBlob(NonNull<sqlite3_blob>);
+
+// SAFETY: The blob is only used while the handle of its connection is
+// locked, whichever thread it is on.
+unsafe impl Send for RawBlob {}
+
+impl RawBlob {
+    /// Closes the blob, committing its writes when the connection is not in
+    /// a transaction.
+    fn close(self) -> c_int {
+        let blob = self.0;
+        std::mem::forget(self);
+        // SAFETY: The blob is closed once.
+        unsafe { sqlite3_blob_close(blob.as_ptr()) }
+    }
+}
+
+impl Drop for RawBlob {
+    fn drop(&mut self) {
+        // SAFETY: The blob is closed once, before its connection is released.
+        unsafe { sqlite3_blob_close(self.0.as_ptr()) };
+    }
+}
+
+/// The connection pinned by a blob handle, with the blob.
+struct BlobState {
+    // Dropped first, a blob must be closed before its connection is released
+    blob: Option<RawBlob>,
+    conn: Option<PoolConnection<Sqlite>>,
+}
+
+/// A handle reading and writing a blob in place, without copying the whole
+/// value. It pins a connection of the pool until it is closed.
+#[derive(Clone, Trace, JsLifetime)]
+#[rquickjs::class]
+pub struct Blob {
+    #[qjs(skip_trace)]
+    state: Arc<Mutex<BlobState>>,
+    #[qjs(skip_trace)]
+    size: c_int,
+    #[qjs(skip_trace)]
+    read_only: bool,
+}
+
+impl Blob {
+    /// Opens the blob stored in `column` of the row `rowid` of `table`, on a
+    /// dedicated connection of the pool.
+    pub async fn open<'js>(
+        ctx: &Ctx<'js>,
+        pool: &SqlitePool,
+        table: &str,
+        column: &str,
+        rowid: Value<'js>,
+        options: BlobOptions,
+    ) -> Result<Self> {
+        let rowid = to_rowid(ctx, rowid)?;
+        let database = to_cstring(ctx, options.database.as_deref().unwrap_or("main"))?;
+        let table = to_cstring(ctx, table)?;
+        let column = to_cstring(ctx, column)?;
+
+        let mut conn = pool.acquire().await.or_throw_sqlite(ctx)?;
+        let (blob, size) = {
+            let mut handle = conn.lock_handle().await.or_throw_sqlite(ctx)?;
+            let db = handle.as_raw_handle();
+            let mut blob: *mut sqlite3_blob = ptr::null_mut();
+            // SAFETY: The handle is locked while the blob is opened.
+            let code = unsafe {
+                sqlite3_blob_open(
+                    db.as_ptr(),
+                    database.as_ptr(),
+                    table.as_ptr(),
+                    column.as_ptr(),
+                    rowid,
+                    (!options.read_only) as c_int,
+                    &mut blob,
+                )
+            };
+            if code != SQLITE_OK {
+                return Err(SqliteError::from_handle(db)
+                    .context("Unable to open blob")
+                    .throw(ctx));
+            }
+            let blob = RawBlob(
+                NonNull::new(blob)
+                    .ok_or_else(|| SqliteError::from_code(SQLITE_NOMEM).throw(ctx))?,
+            );
+            // SAFETY: The handle is locked.
+            let size = unsafe { sqlite3_blob_bytes(blob.0.as_ptr()) };
+            (blob, size)
+        };
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(BlobState {
+                blob: Some(blob),
+                conn: Some(conn),
+            })),
+            size,
+            read_only: options.read_only,
+        })
+    }
+
+    /// Checks that `length` bytes from `offset` are inside the blob.
+    fn range(&self, ctx: &Ctx<'_>, offset: f64, length: f64) -> Result<(c_int, c_int)> {
+        let is_index = |value: f64| value.fract() == 0.0 && value >= 0.0;
+        if !is_index(offset) || !is_index(length) || offset + length > self.size as f64 {
+            return Err(Exception::throw_range(
+                ctx,
+                &[
+                    "Range of ",
+                    &length.to_string(),
+                    " bytes at offset ",
+                    &offset.to_string(),
+                    " is outside of the blob of ",
+                    &self.size.to_string(),
+                    " bytes",
+                ]
+                .concat(),
+            ));
+        }
+        Ok((offset as c_int, length as c_int))
+    }
+}
+
+#[rquickjs::methods(rename_all = "camelCase")]
+impl Blob {
+    /// The size of the blob in bytes, which can not be changed through the handle.
+    #[qjs(get)]
+    fn size(&self) -> c_int {
+        self.size
+    }
+
+    /// Reads `length` bytes from `offset`, until the end of the blob by default.
+    async fn read<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        offset: Opt<f64>,
+        length: Opt<f64>,
+    ) -> Result<TypedArray<'js, u8>> {
+        let offset = offset.0.unwrap_or(0.0);
+        let length = length.0.unwrap_or((self.size as f64 - offset).max(0.0));
+        let (offset, length) = self.range(&ctx, offset, length)?;
+
+        let mut state = self.state.lock().await;
+        let BlobState {
+            blob: Some(blob),
+            conn: Some(conn),
+        } = &mut *state
+        else {
+            return Err(Exception::throw_message(&ctx, "Blob is closed"));
+        };
+        let mut handle = conn.lock_handle().await.or_throw_sqlite(&ctx)?;
+        let mut bytes = vec![0u8; length as usize];
+        // SAFETY: The handle is locked, the buffer has `length` bytes.
+        let code = unsafe {
+            sqlite3_blob_read(
+                blob.0.as_ptr(),
+                bytes.as_mut_ptr() as *mut c_void,
+                length,
+                offset,
+            )
+        };
+        if code != SQLITE_OK {
+            return Err(SqliteError::from_handle(handle.as_raw_handle())
+                .context("Unable to read blob")
+                .throw(&ctx));
+        }
+        TypedArray::new(ctx, bytes)
+    }
+
+    /// Writes `bytes` at `offset`, writes can not grow the blob.
+    async fn write<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        offset: f64,
+        bytes: TypedArray<'js, u8>,
+    ) -> Result<()> {
+        if self.read_only {
+            return Err(Exception::throw_message(&ctx, "Blob is read-only"));
+        }
+        let length = bytes
+            .as_bytes()
+            .ok_or_else(|| Exception::throw_type(&ctx, "Bytes must not be detached"))?
+            .len();
+        let (offset, length) = self.range(&ctx, offset, length as f64)?;
+
+        let mut state = self.state.lock().await;
+        let BlobState {
+            blob: Some(blob),
+            conn: Some(conn),
+        } = &mut *state
+        else {
+            return Err(Exception::throw_message(&ctx, "Blob is closed"));
+        };
+        let mut handle = conn.lock_handle().await.or_throw_sqlite(&ctx)?;
+        let bytes = bytes
+            .as_bytes()
+            .ok_or_else(|| Exception::throw_type(&ctx, "Bytes must not be detached"))?;
+        // The array could have been resized while waiting for the connection
+        if bytes.len() != length as usize {
+            return Err(Exception::throw_type(
+                &ctx,
+                "Bytes were resized while writing",
+            ));
+        }
+        // SAFETY: The handle is locked, SQLite copies `length` bytes.
+        let code = unsafe {
+            sqlite3_blob_write(
+                blob.0.as_ptr(),
+                bytes.as_ptr() as *const c_void,
+                length,
+                offset,
+            )
+        };
+        if code != SQLITE_OK {
+            return Err(SqliteError::from_handle(handle.as_raw_handle())
+                .context("Unable to write blob")
+                .throw(&ctx));
+        }
+        Ok(())
+    }
+
+    /// Closes the blob, committing its writes, and releases its connection.
+    async fn close(&self, ctx: Ctx<'_>) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let Some(mut conn) = state.conn.take() else {
+            return Ok(());
+        };
+        let mut handle = conn.lock_handle().await.or_throw_sqlite(&ctx)?;
+        if let Some(blob) = state.blob.take()
+            && blob.close() != SQLITE_OK
+        {
+            return Err(SqliteError::from_handle(handle.as_raw_handle())
+                .context("Unable to close blob")
+                .throw(&ctx));
+        }
+        Ok(())
+    }
+}
+
+fn to_rowid(ctx: &Ctx<'_>, value: Value<'_>) -> Result<i64> {
+    if let Some(int) = value.as_int() {
+        return Ok(int as i64);
+    } else if let Some(big_int) = value.as_big_int() {
+        return big_int.clone().to_i64();
+    } else if let Some(float) = value.as_float()
+        && float.fract() == 0.0
+        && float.abs() <= super::value::MAX_SAFE_INTEGER as f64
+    {
+        return Ok(float as i64);
+    }
+    Err(Exception::throw_type(ctx, "rowid must be an integer"))
+}
+
+fn to_cstring(ctx: &Ctx<'_>, name: &str) -> Result<CString> {
+    CString::new(name).map_err(|_| {
+        Exception::throw_type(
+            ctx,
+            "Table, column and database names must not contain NUL characters",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rquickjs::CatchResultExt;
+    use rquickjs_extra_test::{ModuleEvaluator, call_test, test_async_with};
+
+    use crate::SqliteModule;
+
+    #[tokio::test]
+    async fn test_blob() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        const fails = async (f) => {
+                            try {
+                                await f();
+                                return "ok";
+                            } catch (e) {
+                                return e.code ?? e.name;
+                            }
+                        };
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE files (id INTEGER PRIMARY KEY, data BLOB)");
+                            await db.exec("INSERT INTO files (id, data) VALUES (1, zeroblob(8))");
+
+                            const blob = await db.openBlob("files", "data", 1);
+                            await blob.write(0, new Uint8Array([1, 2, 3, 4]));
+                            await blob.write(4, new Uint8Array([5, 6, 7, 8]));
+                            const chunk = await blob.read(2, 4);
+                            const rest = await blob.read(6);
+                            const outside = await fails(() => blob.write(6, new Uint8Array([0, 0, 0])));
+                            await blob.close();
+                            const closed = await fails(() => blob.read());
+
+                            const data = await db.prepare("SELECT data FROM files WHERE id = 1").then((s) => s.pluck().get());
+                            const readOnly = await db.openBlob("files", "data", 1n, { readOnly: true });
+                            const denied = await fails(() => readOnly.write(0, new Uint8Array([0])));
+                            const all = await readOnly.read();
+                            await readOnly.close();
+                            const missing = await fails(() => db.openBlob("files", "data", 2));
+                            return [
+                                blob.size,
+                                chunk.join(""),
+                                rest.join(""),
+                                outside,
+                                closed,
+                                data.join(""),
+                                denied,
+                                all.length,
+                                missing,
+                            ].join();
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    "8,3456,78,RangeError,Error,12345678,Error,8,SQLITE_ERROR"
+                );
+            })
+        })
+        .await;
+    }
+}
@@ -0,0 +1,398 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::c_int;
+use std::ptr::NonNull;
+use std::rc::Rc;
+use std::sync::mpsc::{SyncSender, sync_channel};
+
+use libsqlite3_sys::{sqlite3, sqlite3_context, sqlite3_value};
+use rquickjs::{CaughtError, Ctx, Function, JsLifetime, Persistent, Value};
+use tokio::sync::mpsc;
+
+use super::TARGET;
+use super::aggregate::{self, Aggregate};
+use super::collation::{self, RawText};
+use super::function;
+use super::hooks::{Event, Hook};
+#[cfg(feature = "session")]
+use super::session::{self, ApplyOptions};
+
+/// A call from a SQLite worker thread that must run on the JS context.
+///
+/// The worker thread is blocked until the call is answered, so the raw
+/// pointers it carries stay valid while the JS context handles it. Events
+/// are the exception, they are only notified and never answered.
+pub enum Call {
+    Function {
+        id: usize,
+        context: RawContext,
+        args: RawArgs,
+        done: SyncSender<()>,
+    },
+    Step {
+        id: usize,
+        state: u64,
+        inverse: bool,
+        context: RawContext,
+        args: RawArgs,
+        done: SyncSender<()>,
+    },
+    Result {
+        id: usize,
+        state: Option<u64>,
+        finish: bool,
+        context: RawContext,
+        done: SyncSender<()>,
+    },
+    Collation {
+        id: usize,
+        db: usize,
+        left: RawText,
+        right: RawText,
+        done: SyncSender<Option<c_int>>,
+    },
+    #[cfg(feature = "session")]
+    Filter {
+        id: usize,
+        db: usize,
+        table: String,
+        done: SyncSender<Option<bool>>,
+    },
+    #[cfg(feature = "session")]
+    Conflict {
+        id: usize,
+        db: usize,
+        kind: c_int,
+        table: Option<String>,
+        done: SyncSender<Option<c_int>>,
+    },
+    Event(Event),
+}
+
+/// A JS callback that can be called from SQL.
+#[derive(JsLifetime)]
+pub enum Callback<'js> {
+    Function(Function<'js>),
+    Aggregate(Aggregate<'js>),
+    Collation(Function<'js>),
+    #[cfg(feature = "session")]
+    Changeset(ApplyOptions<'js>),
+    Hook {
+        hook: Hook,
+        listener: Function<'js>,
+        safe_integers: bool,
+    },
+}
+
+/// The JS state owned by the dispatcher task.
+struct State<'js> {
+    /// Ordered by id so listeners are called in registration order.
+    callbacks: BTreeMap<usize, Callback<'js>>,
+    /// Accumulators of the aggregates in progress, by aggregate context.
+    accumulators: HashMap<u64, Value<'js>>,
+}
+
+pub struct RawContext(pub *mut sqlite3_context);
+
+pub struct RawArgs(pub *mut *mut sqlite3_value, pub c_int);
+
+// SAFETY: The pointers are only used by the JS context while the SQLite
+// worker thread that owns them is blocked waiting for the answer.
+unsafe impl Send for RawContext {}
+unsafe impl Send for RawArgs {}
+
+impl RawArgs {
+    pub fn as_slice(&self) -> &[*mut sqlite3_value] {
+        if self.0.is_null() || self.1 <= 0 {
+            return &[];
+        }
+        // SAFETY: SQLite passes `argc` valid values in `argv`.
+        unsafe { std::slice::from_raw_parts(self.0, self.1 as usize) }
+    }
+}
+
+/// Handle used by SQLite worker threads to call into the JS context.
+#[derive(Clone)]
+pub struct Caller {
+    sender: mpsc::UnboundedSender<Call>,
+}
+
+impl Caller {
+    /// Sends a call to the JS context and blocks until it is answered.
+    /// Returns `None` if the database was closed.
+    pub fn call<T>(&self, call: impl FnOnce(SyncSender<T>) -> Call) -> Option<T> {
+        let (sender, receiver) = sync_channel(1);
+        self.sender.send(call(sender)).ok()?;
+        receiver.recv().ok()
+    }
+
+    /// Sends a call to the JS context without waiting for it to be handled.
+    pub fn notify(&self, call: Call) {
+        let _ = self.sender.send(call);
+    }
+}
+
+struct Channels {
+    caller: Caller,
+    registrations: mpsc::UnboundedSender<(usize, Option<Persistent<Callback<'static>>>)>,
+}
+
+/// Runs the JS callbacks of a database on its context.
+///
+/// The callbacks are owned by a task spawned on the context with the first
+/// registration, the task stops when the database is closed.
+#[derive(Clone, Default)]
+pub struct Dispatcher {
+    channels: Rc<RefCell<Option<Channels>>>,
+    next_id: Rc<RefCell<usize>>,
+}
+
+impl Dispatcher {
+    pub fn next_id(&self) -> usize {
+        let mut next_id = self.next_id.borrow_mut();
+        *next_id += 1;
+        *next_id
+    }
+
+    pub fn caller(&self, ctx: &Ctx<'_>) -> Caller {
+        self.channels
+            .borrow_mut()
+            .get_or_insert_with(|| Self::spawn(ctx))
+            .caller
+            .clone()
+    }
+
+    pub fn register<'js>(&self, ctx: &Ctx<'js>, id: usize, callback: Callback<'js>) {
+        let mut channels = self.channels.borrow_mut();
+        let channels = channels.get_or_insert_with(|| Self::spawn(ctx));
+        let _ = channels
+            .registrations
+            .send((id, Some(Persistent::save(ctx, callback))));
+    }
+
+    pub fn unregister(&self, id: usize) {
+        if let Some(channels) = self.channels.borrow().as_ref() {
+            let _ = channels.registrations.send((id, None));
+        }
+    }
+
+    pub fn close(&self) {
+        self.channels.borrow_mut().take();
+    }
+
+    fn spawn(ctx: &Ctx<'_>) -> Channels {
+        let (sender, mut calls) = mpsc::unbounded_channel();
+        let (registrations, mut registered) = mpsc::unbounded_channel();
+
+        let task_ctx = ctx.clone();
+        ctx.spawn(async move {
+            let ctx = task_ctx;
+            let mut state = State {
+                callbacks: BTreeMap::new(),
+                accumulators: HashMap::new(),
+            };
+            loop {
+                tokio::select! {
+                    biased;
+                    registration = registered.recv() => {
+                        let Some((id, callback)) = registration else {
+                            break;
+                        };
+                        match callback {
+                            Some(callback) => {
+                                if let Ok(callback) = Persistent::<Callback>::restore(callback, &ctx) {
+                                    state.callbacks.insert(id, callback);
+                                }
+                            }
+                            None => {
+                                state.callbacks.remove(&id);
+                            }
+                        }
+                    }
+                    Some(call) = calls.recv() => Self::handle(&ctx, &mut state, call),
+                }
+            }
+        });
+
+        Channels {
+            caller: Caller { sender },
+            registrations,
+        }
+    }
+
+    fn handle<'js>(ctx: &Ctx<'js>, state: &mut State<'js>, call: Call) {
+        match call {
+            Call::Function {
+                id,
+                context,
+                args,
+                done,
+            } => {
+                let callback = match state.callbacks.get(&id) {
+                    Some(Callback::Function(callback)) => Some(callback),
+                    _ => None,
+                };
+                function::call(ctx, callback, &context, &args);
+                let _ = done.send(());
+            }
+            Call::Step {
+                id,
+                state: key,
+                inverse,
+                context,
+                args,
+                done,
+            } => {
+                let aggregate = match state.callbacks.get(&id) {
+                    Some(Callback::Aggregate(aggregate)) => Some(aggregate),
+                    _ => None,
+                };
+                aggregate::step(
+                    ctx,
+                    aggregate,
+                    &mut state.accumulators,
+                    key,
+                    inverse,
+                    &context,
+                    &args,
+                );
+                let _ = done.send(());
+            }
+            Call::Result {
+                id,
+                state: key,
+                finish,
+                context,
+                done,
+            } => {
+                let aggregate = match state.callbacks.get(&id) {
+                    Some(Callback::Aggregate(aggregate)) => Some(aggregate),
+                    _ => None,
+                };
+                aggregate::result(
+                    ctx,
+                    aggregate,
+                    &mut state.accumulators,
+                    key,
+                    finish,
+                    &context,
+                );
+                let _ = done.send(());
+            }
+            Call::Collation {
+                id,
+                db,
+                left,
+                right,
+                done,
+            } => {
+                let callback = match state.callbacks.get(&id) {
+                    Some(Callback::Collation(callback)) => Some(callback),
+                    _ => None,
+                };
+                let result = collation::call(ctx, callback, &left, &right);
+                let _ = done.send(fail(ctx, db, result));
+            }
+            #[cfg(feature = "session")]
+            Call::Filter {
+                id,
+                db,
+                table,
+                done,
+            } => {
+                let options = match state.callbacks.get(&id) {
+                    Some(Callback::Changeset(options)) => Some(options),
+                    _ => None,
+                };
+                let result = session::filter(ctx, options, &table);
+                let _ = done.send(fail(ctx, db, result));
+            }
+            #[cfg(feature = "session")]
+            Call::Conflict {
+                id,
+                db,
+                kind,
+                table,
+                done,
+            } => {
+                let options = match state.callbacks.get(&id) {
+                    Some(Callback::Changeset(options)) => Some(options),
+                    _ => None,
+                };
+                let result = session::conflict(ctx, options, kind, table.as_deref());
+                let _ = done.send(fail(ctx, db, result));
+            }
+            Call::Event(event) => {
+                for callback in state.callbacks.values() {
+                    if let Callback::Hook {
+                        hook,
+                        listener,
+                        safe_integers,
+                    } = callback
+                        && *hook == event.hook()
+                        && let Err(err) = event.deliver(ctx, listener, *safe_integers)
+                    {
+                        log::error!(target: TARGET, "{}", error_message(ctx, err));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The errors thrown by JS callbacks that must stop the query running on a
+/// connection, by connection handle. SQLite can only be told that a callback
+/// failed, so the query throws the error once SQLite returns.
+#[derive(Default, JsLifetime)]
+struct Failures<'js>(RefCell<HashMap<usize, Option<Value<'js>>>>);
+
+/// Starts recording the first error of the callbacks called by the query
+/// about to run on `db`, until [`unwatch`].
+pub fn watch(ctx: &Ctx<'_>, db: NonNull<sqlite3>) {
+    if ctx.userdata::<Failures>().is_none() {
+        let _ = ctx.store_userdata(Failures::default());
+    }
+    if let Some(failures) = ctx.userdata::<Failures>() {
+        failures.0.borrow_mut().insert(db.as_ptr() as usize, None);
+    }
+}
+
+/// Stops recording the errors of the callbacks called by the query of `db`,
+/// returns the first one thrown again.
+pub fn unwatch(ctx: &Ctx<'_>, db: NonNull<sqlite3>) -> Option<rquickjs::Error> {
+    let failures = ctx.userdata::<Failures>()?;
+    let error = failures.0.borrow_mut().remove(&(db.as_ptr() as usize))??;
+    Some(ctx.throw(error))
+}
+
+/// Records the error of a callback called by the query of `db`, or logs it
+/// when nothing watches the connection. Returns `None` on errors, so the
+/// worker thread stops the query.
+fn fail<T>(ctx: &Ctx<'_>, db: usize, result: rquickjs::Result<T>) -> Option<T> {
+    let err = match result {
+        Ok(value) => return Some(value),
+        Err(err) => err,
+    };
+    let failures = ctx.userdata::<Failures>();
+    let mut failures = failures.as_ref().map(|failures| failures.0.borrow_mut());
+    match failures.as_mut().and_then(|failures| failures.get_mut(&db)) {
+        Some(Some(_)) => {}
+        Some(error) => {
+            // Catch the exception so it is not left pending on the context
+            let _ = CaughtError::from_error(ctx, err).throw(ctx);
+            *error = Some(ctx.catch());
+        }
+        None => log::error!(target: TARGET, "{}", error_message(ctx, err)),
+    }
+    None
+}
+
+/// Formats a JS error so it can be reported through SQLite.
+pub fn error_message(ctx: &Ctx<'_>, err: rquickjs::Error) -> String {
+    match CaughtError::from_error(ctx, err) {
+        CaughtError::Exception(exception) => exception
+            .message()
+            .unwrap_or_else(|| "Unknown exception".into()),
+        err => err.to_string(),
+    }
+}
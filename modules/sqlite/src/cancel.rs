@@ -0,0 +1,333 @@
+use std::ffi::{c_int, c_void};
+use std::ptr::{self, NonNull};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use libsqlite3_sys::{SQLITE_INTERRUPT, sqlite3, sqlite3_progress_handler};
+use rquickjs::{Ctx, Exception, Function, Object, Result, Value, function::This};
+use sqlx::SqliteConnection;
+
+use super::callback;
+use super::error::{ResultExt as _, SqliteError};
+use super::parameters::{Parameters, is_plain_object};
+
+/// Number of virtual machine instructions between checks of the abort state.
+const PROGRESS_INSTRUCTIONS: c_int = 1000;
+
+/// The `{ signal, timeout }` options of a query.
+#[derive(Default)]
+pub struct QueryOptions<'js> {
+    signal: Option<Object<'js>>,
+    timeout: Option<Duration>,
+}
+
+impl<'js> QueryOptions<'js> {
+    pub fn from_js(ctx: &Ctx<'js>, value: Option<Value<'js>>) -> Result<Self> {
+        let Some(value) = value.filter(|value| !value.is_undefined()) else {
+            return Ok(Self::default());
+        };
+        let Some(obj) = value.into_object() else {
+            return Err(Exception::throw_type(
+                ctx,
+                "Query options must be an object",
+            ));
+        };
+
+        let signal =
+            match obj.get::<_, Value<'js>>("signal")? {
+                value if value.is_undefined() || value.is_null() => None,
+                value => {
+                    let signal = value.into_object().filter(|signal| {
+                        signal
+                            .get::<_, Value<'js>>("addEventListener")
+                            .is_ok_and(|value| value.is_function())
+                    });
+                    Some(signal.ok_or_else(|| {
+                        Exception::throw_type(ctx, "signal must be an AbortSignal")
+                    })?)
+                }
+            };
+        let timeout = match obj.get::<_, Value<'js>>("timeout")? {
+            value if value.is_undefined() || value.is_null() => None,
+            value => match value.as_number() {
+                Some(ms) if ms.is_finite() && ms >= 0.0 => {
+                    Some(Duration::from_secs_f64(ms / 1000.0))
+                }
+                _ => {
+                    return Err(Exception::throw_type(
+                        ctx,
+                        "timeout must be a non-negative number of milliseconds",
+                    ));
+                }
+            },
+        };
+        Ok(Self { signal, timeout })
+    }
+
+    /// Removes the options trailing the parameters of a statement, if any.
+    ///
+    /// A trailing object is only read as options when its keys are all
+    /// `signal` or `timeout`, none of them names a parameter, and the values
+    /// before it are enough to bind the statement.
+    pub fn split(
+        ctx: &Ctx<'js>,
+        parameters: &Parameters,
+        params: &mut Vec<Value<'js>>,
+    ) -> Result<Self> {
+        let Some((last, values)) = params.split_last() else {
+            return Ok(Self::default());
+        };
+        if !is_plain_object(ctx, last)? || !parameters.accepts(ctx, values)? {
+            return Ok(Self::default());
+        }
+        let keys = last
+            .as_object()
+            .into_iter()
+            .flat_map(|obj| obj.keys::<String>());
+        for key in keys {
+            let key = key?;
+            if !matches!(key.as_str(), "signal" | "timeout") || parameters.is_parameter(&key) {
+                return Ok(Self::default());
+            }
+        }
+        Self::from_js(ctx, params.pop())
+    }
+
+    /// Prepares `conn` to interrupt the next query when the signal is
+    /// aborted or the timeout expires, and to throw the errors of the
+    /// callbacks it calls. [`Abort::finish`] must be called with its result.
+    ///
+    /// The abort state is checked by a progress handler rather than with
+    /// `sqlite3_interrupt`, which is lost if it arrives before the statement
+    /// starts running on the worker thread.
+    pub async fn start(&self, ctx: &Ctx<'js>, conn: &mut SqliteConnection) -> Result<Abort<'js>> {
+        if let Some(signal) = &self.signal
+            && signal.get::<_, Option<bool>>("aborted")?.unwrap_or(false)
+        {
+            return Err(abort_error(ctx, self.signal.as_ref(), None));
+        }
+        let db = conn
+            .lock_handle()
+            .await
+            .or_throw_sqlite(ctx)?
+            .as_raw_handle();
+        if self.signal.is_none() && self.timeout.is_none() {
+            callback::watch(ctx, db);
+            return Ok(Abort { db, armed: None });
+        }
+
+        let state = Arc::new(AbortState {
+            aborted: AtomicBool::new(false),
+            deadline: self.timeout.map(|timeout| Instant::now() + timeout),
+            disabled: AtomicBool::new(false),
+        });
+        let listener = match &self.signal {
+            Some(signal) => {
+                let state = state.clone();
+                let listener = Function::new(ctx.clone(), move || {
+                    state.aborted.store(true, Ordering::Relaxed);
+                })?;
+                listen(signal, "addEventListener", &listener)?;
+                Some((signal.clone(), listener))
+            }
+            None => None,
+        };
+        let handler = match ProgressHandler::install(conn, state).await {
+            Ok(handler) => handler,
+            Err(err) => {
+                if let Some((signal, listener)) = &listener {
+                    listen(signal, "removeEventListener", listener)?;
+                }
+                return Err(err).or_throw_sqlite(ctx);
+            }
+        };
+        callback::watch(ctx, db);
+        Ok(Abort {
+            db,
+            armed: Some(Armed {
+                handler,
+                listener,
+                timeout: self.timeout,
+            }),
+        })
+    }
+}
+
+/// The abort state of a running query.
+pub struct Abort<'js> {
+    db: NonNull<sqlite3>,
+    armed: Option<Armed<'js>>,
+}
+
+struct Armed<'js> {
+    handler: ProgressHandler,
+    listener: Option<(Object<'js>, Function<'js>)>,
+    timeout: Option<Duration>,
+}
+
+impl<'js> Abort<'js> {
+    /// Stops watching for aborts and converts the result of the query,
+    /// throwing the error of a callback that failed, or an `AbortError` if
+    /// it was interrupted.
+    pub async fn finish<T>(
+        self,
+        ctx: &Ctx<'js>,
+        conn: &mut SqliteConnection,
+        result: std::result::Result<T, sqlx::Error>,
+        sql: &str,
+    ) -> Result<T> {
+        let failure = callback::unwatch(ctx, self.db);
+        let Some(armed) = self.armed else {
+            return match failure {
+                Some(err) => Err(err),
+                None => result.or_throw_sql(ctx, sql),
+            };
+        };
+        let state = armed.handler.state.clone();
+        armed.handler.remove(conn).await;
+        if let Some((signal, listener)) = &armed.listener {
+            listen(signal, "removeEventListener", listener)?;
+        }
+        if let Some(err) = failure {
+            return Err(err);
+        }
+
+        let interrupted = match &result {
+            Err(err) => SqliteError::from_sqlx(err)
+                .is_some_and(|err| err.primary_code() == SQLITE_INTERRUPT),
+            Ok(_) => false,
+        };
+        if interrupted {
+            let signal = armed.listener.as_ref().map(|(signal, _)| signal);
+            if state.aborted.load(Ordering::Relaxed) {
+                return Err(abort_error(ctx, signal, None));
+            }
+            if state.is_expired() {
+                return Err(abort_error(ctx, None, armed.timeout));
+            }
+        }
+        result.or_throw_sql(ctx, sql)
+    }
+}
+
+/// Adds or removes `listener` as listener of the abort event of `signal`.
+fn listen<'js>(signal: &Object<'js>, method: &str, listener: &Function<'js>) -> Result<()> {
+    signal
+        .get::<_, Function<'js>>(method)?
+        .call((This(signal.clone()), "abort", listener.clone()))
+}
+
+/// Throws an `AbortError`, caused by the reason of `signal` or the expiry
+/// of `timeout`.
+fn abort_error<'js>(
+    ctx: &Ctx<'js>,
+    signal: Option<&Object<'js>>,
+    timeout: Option<Duration>,
+) -> rquickjs::Error {
+    let error = (|| {
+        let message = match timeout {
+            Some(timeout) => [
+                "Query timed out after ",
+                &timeout.as_millis().to_string(),
+                " ms",
+            ]
+            .concat(),
+            None => "Query was aborted".to_owned(),
+        };
+        let error = Exception::from_message(ctx.clone(), &message)?;
+        error.set("name", "AbortError")?;
+        error.set("code", "ABORT_ERR")?;
+        if let Some(signal) = signal {
+            let reason = signal.get::<_, Value<'js>>("reason")?;
+            if !reason.is_undefined() {
+                error.set("cause", reason)?;
+            }
+        }
+        Ok(error)
+    })();
+    match error {
+        Ok(error) => ctx.throw(error.into_value()),
+        Err(err) => err,
+    }
+}
+
+struct AbortState {
+    aborted: AtomicBool,
+    deadline: Option<Instant>,
+    /// Set when the handler is dropped without being removed.
+    disabled: AtomicBool,
+}
+
+impl AbortState {
+    fn is_interrupted(&self) -> bool {
+        !self.disabled.load(Ordering::Relaxed)
+            && (self.aborted.load(Ordering::Relaxed) || self.is_expired())
+    }
+
+    fn is_expired(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// A progress handler interrupting the statements of a connection once
+/// its abort state is set, which should be removed before it is dropped.
+struct ProgressHandler {
+    db: NonNull<sqlite3>,
+    state: Arc<AbortState>,
+    removed: bool,
+}
+
+impl ProgressHandler {
+    async fn install(
+        conn: &mut SqliteConnection,
+        state: Arc<AbortState>,
+    ) -> std::result::Result<Self, sqlx::Error> {
+        let mut handle = conn.lock_handle().await?;
+        let db = handle.as_raw_handle();
+        // SAFETY: The handle is locked, the state outlives the handler as it is
+        // only dropped once the handler is removed.
+        unsafe {
+            sqlite3_progress_handler(
+                db.as_ptr(),
+                PROGRESS_INSTRUCTIONS,
+                Some(progress),
+                Arc::as_ptr(&state) as *mut c_void,
+            )
+        };
+        Ok(Self {
+            db,
+            state,
+            removed: false,
+        })
+    }
+
+    async fn remove(mut self, conn: &mut SqliteConnection) {
+        // When the worker is gone, the handler is left to drop in case the
+        // connection is still used.
+        if let Ok(_handle) = conn.lock_handle().await {
+            // SAFETY: The handle is locked.
+            unsafe { sqlite3_progress_handler(self.db.as_ptr(), 0, None, ptr::null_mut()) };
+            self.removed = true;
+        }
+    }
+}
+
+impl Drop for ProgressHandler {
+    fn drop(&mut self) {
+        if !self.removed {
+            // The handler can not be removed without locking the connection,
+            // it is disabled instead and its state is leaked as SQLite keeps
+            // calling it until another handler replaces it.
+            self.state.disabled.store(true, Ordering::Relaxed);
+            std::mem::forget(self.state.clone());
+        }
+    }
+}
+
+unsafe extern "C" fn progress(data: *mut c_void) -> c_int {
+    // SAFETY: `data` is the state of the installed handler.
+    let state = unsafe { &*(data as *const AbortState) };
+    state.is_interrupted() as c_int
+}
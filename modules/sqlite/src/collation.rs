@@ -0,0 +1,124 @@
+use std::cmp::Ordering;
+use std::ffi::{CString, c_int, c_void};
+use std::ptr::NonNull;
+
+use libsqlite3_sys::{
+    SQLITE_OK, SQLITE_UTF8, sqlite3, sqlite3_create_collation_v2, sqlite3_interrupt,
+};
+use rquickjs::{Ctx, Exception, Function, Result, Value};
+
+use super::callback::{Call, Caller};
+use super::error::SqliteError;
+use super::function::check;
+
+/// Text compared by a collation, borrowed from SQLite.
+pub struct RawText(pub *const c_void, pub c_int);
+
+// SAFETY: The text is only read by the JS context while the SQLite worker
+// thread that owns it is blocked waiting for the answer.
+unsafe impl Send for RawText {}
+
+impl RawText {
+    fn to_str(&self) -> std::borrow::Cow<'_, str> {
+        if self.0.is_null() || self.1 <= 0 {
+            return "".into();
+        }
+        // SAFETY: SQLite passes `len` valid bytes.
+        let bytes = unsafe { std::slice::from_raw_parts(self.0 as *const u8, self.1 as usize) };
+        String::from_utf8_lossy(bytes)
+    }
+}
+
+struct CollationHandle {
+    id: usize,
+    db: NonNull<sqlite3>,
+    caller: Caller,
+}
+
+/// Creates the installer of a collation calling the JS comparator `id`.
+pub fn installer(
+    ctx: &Ctx<'_>,
+    name: String,
+    id: usize,
+    caller: Caller,
+) -> Result<impl Fn(NonNull<sqlite3>) -> std::result::Result<(), SqliteError> + Send + Sync + 'static>
+{
+    let name = CString::new(name).map_err(|_| {
+        Exception::throw_type(ctx, "Collation name must not contain NUL characters")
+    })?;
+    Ok(move |db: NonNull<sqlite3>| {
+        let handle = Box::into_raw(Box::new(CollationHandle {
+            id,
+            db,
+            caller: caller.clone(),
+        }));
+        // SAFETY: The connection handle is locked by the caller. SQLite owns
+        // the handle from now on and frees it with `destroy_collation`.
+        let code = unsafe {
+            sqlite3_create_collation_v2(
+                db.as_ptr(),
+                name.as_ptr(),
+                SQLITE_UTF8,
+                handle as *mut c_void,
+                Some(call_collation),
+                Some(destroy_collation),
+            )
+        };
+        if code != SQLITE_OK {
+            // Unlike functions, the handle is not destroyed when creating fails
+            drop(unsafe { Box::from_raw(handle) });
+        }
+        check(code)
+    })
+}
+
+unsafe extern "C" fn call_collation(
+    data: *mut c_void,
+    left_len: c_int,
+    left: *const c_void,
+    right_len: c_int,
+    right: *const c_void,
+) -> c_int {
+    // SAFETY: The user data is the handle given to sqlite3_create_collation_v2.
+    let handle = unsafe { &*(data as *const CollationHandle) };
+    let order = handle.caller.call(|done| Call::Collation {
+        id: handle.id,
+        db: handle.db.as_ptr() as usize,
+        left: RawText(left, left_len),
+        right: RawText(right, right_len),
+        done,
+    });
+    order.flatten().unwrap_or_else(|| {
+        // SQLite has no way to report errors from collations, the statement
+        // is interrupted and throws the error of the comparator instead.
+        // SAFETY: The collation is called by a statement of the connection.
+        unsafe { sqlite3_interrupt(handle.db.as_ptr()) };
+        0
+    })
+}
+
+unsafe extern "C" fn destroy_collation(data: *mut c_void) {
+    drop(unsafe { Box::from_raw(data as *mut CollationHandle) });
+}
+
+/// Compares two texts with a collation on the JS context.
+pub fn call<'js>(
+    ctx: &Ctx<'js>,
+    callback: Option<&Function<'js>>,
+    left: &RawText,
+    right: &RawText,
+) -> Result<c_int> {
+    let callback = callback.ok_or_else(|| Exception::throw_message(ctx, "Unknown collation"))?;
+    let value = callback.call::<_, Value>((left.to_str().as_ref(), right.to_str().as_ref()))?;
+    let Some(number) = value.as_number() else {
+        return Err(Exception::throw_type(
+            ctx,
+            "Collations must return a number",
+        ));
+    };
+    Ok(match number.partial_cmp(&0.0) {
+        Some(Ordering::Less) => -1,
+        Some(Ordering::Greater) => 1,
+        _ => 0,
+    })
+}
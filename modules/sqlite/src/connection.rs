@@ -0,0 +1,147 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rquickjs::{Ctx, Exception, Result};
+use sqlx::pool::PoolConnection;
+use sqlx::{Sqlite, SqliteConnection, SqlitePool};
+use tokio::sync::{Mutex, OwnedMappedMutexGuard, OwnedMutexGuard};
+
+use super::error::ResultExt as _;
+#[cfg(feature = "session")]
+use super::session::SessionState;
+use super::transaction::TransactionState;
+
+/// Where queries are executed: any connection of the pool or the
+/// connection pinned by an open transaction or session.
+#[derive(Clone)]
+pub enum Connection {
+    Pool(SqlitePool),
+    Transaction {
+        state: Arc<Mutex<TransactionState>>,
+        id: u64,
+        iterating: Iterating,
+    },
+    #[cfg(feature = "session")]
+    Session {
+        state: Arc<Mutex<SessionState>>,
+        iterating: Iterating,
+    },
+}
+
+pub enum ConnectionGuard {
+    Pool(PoolConnection<Sqlite>),
+    Transaction(OwnedMappedMutexGuard<TransactionState, sqlx::Transaction<'static, Sqlite>>),
+    #[cfg(feature = "session")]
+    Session(OwnedMappedMutexGuard<SessionState, PoolConnection<Sqlite>>),
+}
+
+impl Connection {
+    pub async fn acquire(&self, ctx: &Ctx<'_>) -> Result<ConnectionGuard> {
+        match self {
+            Connection::Pool(pool) => Ok(ConnectionGuard::Pool(
+                pool.acquire().await.or_throw_sqlite(ctx)?,
+            )),
+            Connection::Transaction {
+                state,
+                id,
+                iterating,
+            } => {
+                let state = iterating.lock(ctx, state).await?;
+                let tx = OwnedMutexGuard::try_map(state, |state| state.connection(*id)).map_err(
+                    |_| Exception::throw_message(ctx, "Transaction is already finished"),
+                )?;
+                Ok(ConnectionGuard::Transaction(tx))
+            }
+            #[cfg(feature = "session")]
+            Connection::Session { state, iterating } => {
+                let state = iterating.lock(ctx, state).await?;
+                let conn = OwnedMutexGuard::try_map(state, |state| state.connection())
+                    .map_err(|_| Exception::throw_message(ctx, "Session is closed"))?;
+                Ok(ConnectionGuard::Session(conn))
+            }
+        }
+    }
+
+    /// The mark of the pinned connection held by its iterators.
+    pub fn iterating(&self) -> Option<Iterating> {
+        match self {
+            Connection::Pool(_) => None,
+            Connection::Transaction { iterating, .. } => Some(iterating.clone()),
+            #[cfg(feature = "session")]
+            Connection::Session { iterating, .. } => Some(iterating.clone()),
+        }
+    }
+}
+
+/// Whether an open iterator holds the connection pinned by a transaction
+/// or session, shared by all of their handles.
+#[derive(Clone, Default)]
+pub struct Iterating(Arc<AtomicBool>);
+
+impl Iterating {
+    /// Locks the state of a pinned connection.
+    ///
+    /// Iterators hold the connection until they are done, which only happens
+    /// once the context consumes them, so waiting for it would never end.
+    pub async fn lock<T>(
+        &self,
+        ctx: &Ctx<'_>,
+        state: &Arc<Mutex<T>>,
+    ) -> Result<OwnedMutexGuard<T>> {
+        match state.clone().try_lock_owned() {
+            Ok(guard) => Ok(guard),
+            Err(_) if self.0.load(Ordering::Relaxed) => Err(Exception::throw_message(
+                ctx,
+                "Connection is busy with an open iterator",
+            )),
+            Err(_) => Ok(state.clone().lock_owned().await),
+        }
+    }
+
+    /// Marks the connection as held by an iterator until the returned guard
+    /// is dropped, so other queries fail instead of waiting for it.
+    pub fn hold(&self) -> Holding {
+        self.0.store(true, Ordering::Relaxed);
+        Holding(self.clone())
+    }
+
+    /// Clears the mark once an iterator is closed, its connection is about
+    /// to be released so queries can wait for it.
+    pub fn release(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Clears the mark of a connection held by an iterator on drop.
+pub struct Holding(Iterating);
+
+impl Drop for Holding {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+impl Deref for ConnectionGuard {
+    type Target = SqliteConnection;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            ConnectionGuard::Pool(conn) => conn,
+            ConnectionGuard::Transaction(tx) => tx,
+            #[cfg(feature = "session")]
+            ConnectionGuard::Session(conn) => conn,
+        }
+    }
+}
+
+impl DerefMut for ConnectionGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            ConnectionGuard::Pool(conn) => conn,
+            ConnectionGuard::Transaction(tx) => tx,
+            #[cfg(feature = "session")]
+            ConnectionGuard::Session(conn) => conn,
+        }
+    }
+}
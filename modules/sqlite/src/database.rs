@@ -1,39 +1,416 @@
-use rquickjs::{Ctx, JsLifetime, Result, class::Trace};
-use rquickjs_extra_utils::result::ResultExt;
-use sqlx::{Executor, SqlitePool};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::rc::Rc;
 
-use super::Statement;
+use rquickjs::{
+    Class, Ctx, Function, JsLifetime, Object, Result, TypedArray, Value,
+    class::Trace,
+    function::{Opt, This},
+};
+use rquickjs_extra_utils::ffi::CVec;
+use sqlx::SqlitePool;
+
+use super::aggregate::{self, Aggregate};
+use super::backup::{self, BackupOptions};
+use super::blob::{Blob, BlobOptions};
+use super::callback::{Callback, Dispatcher};
+use super::cancel::QueryOptions;
+use super::collation;
+use super::connection::Connection;
+use super::error::ResultExt as _;
+#[cfg(feature = "sqlite-extensions")]
+use super::extension;
+use super::function::{self, FunctionOptions};
+use super::hooks::Hook;
+use super::migrate::{self, MigrateOptions, Migration};
+use super::pragma::{self, PragmaOptions};
+use super::registry::Registry;
+use super::serialize;
+#[cfg(feature = "session")]
+use super::session::{self, ApplyOptions, Session, SessionOptions};
+use super::value::ReadOptions;
+use super::{Statement, Transaction};
 
 #[derive(Clone, Trace, JsLifetime)]
 #[rquickjs::class]
 pub struct Database {
     #[qjs(skip_trace)]
     pool: SqlitePool,
+    #[qjs(skip_trace)]
+    registry: Registry,
+    #[qjs(skip_trace)]
+    dispatcher: Dispatcher,
+    #[qjs(skip_trace)]
+    hooks: Rc<RefCell<HashSet<Hook>>>,
+    #[qjs(skip_trace)]
+    options: Cell<ReadOptions>,
+    #[qjs(skip_trace)]
+    allow_extension: bool,
 }
 
 impl Database {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        Self::with_options(pool, Registry::default(), ReadOptions::default(), false)
+    }
+
+    pub(crate) fn with_options(
+        pool: SqlitePool,
+        registry: Registry,
+        options: ReadOptions,
+        allow_extension: bool,
+    ) -> Self {
+        Self {
+            pool,
+            registry,
+            dispatcher: Dispatcher::default(),
+            hooks: Rc::default(),
+            options: Cell::new(options),
+            allow_extension,
+        }
+    }
+
+    /// Registers a listener of `hook`, installing the hook on every
+    /// connection the first time. Returns a function removing the listener.
+    async fn listen<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        hook: Hook,
+        listener: Function<'js>,
+    ) -> Result<Function<'js>> {
+        if !self.hooks.borrow().contains(&hook) {
+            let install = hook.installer(self.dispatcher.caller(ctx));
+            let mut conn = self.pool.acquire().await.or_throw_sqlite(ctx)?;
+            self.registry
+                .add(&mut conn, install)
+                .await
+                .map_err(|err| err.context("Unable to register hook").throw(ctx))?;
+            self.hooks.borrow_mut().insert(hook);
+        }
+
+        let id = self.dispatcher.next_id();
+        self.dispatcher.register(
+            ctx,
+            id,
+            Callback::Hook {
+                hook,
+                listener,
+                safe_integers: self.options.get().safe_integers,
+            },
+        );
+        let dispatcher = self.dispatcher.clone();
+        Function::new(ctx.clone(), move || dispatcher.unregister(id))
     }
 }
 
+#[cfg(not(feature = "session"))]
+fn session_disabled(ctx: &Ctx<'_>) -> rquickjs::Error {
+    rquickjs::Exception::throw_message(
+        ctx,
+        "Sessions are not available, rquickjs-extra-sqlite must be built with the session feature",
+    )
+}
+
 #[rquickjs::methods(rename_all = "camelCase")]
 impl Database {
-    async fn exec(&self, ctx: Ctx<'_>, sql: String) -> Result<()> {
-        sqlx::raw_sql(&sql)
-            .execute(&self.pool)
-            .await
-            .or_throw(&ctx)?;
+    async fn exec<'js>(&self, ctx: Ctx<'js>, sql: String, options: Opt<Value<'js>>) -> Result<()> {
+        let options = QueryOptions::from_js(&ctx, options.0)?;
+        let mut conn = self.pool.acquire().await.or_throw_sqlite(&ctx)?;
+        let abort = options.start(&ctx, &mut conn).await?;
+        let result = sqlx::raw_sql(&sql).execute(&mut *conn).await;
+        abort.finish(&ctx, &mut conn, result, &sql).await?;
         Ok(())
     }
 
+    /// Runs a pragma and returns its rows, or its first value with `simple`.
+    /// Settings of the connection are applied to every connection of the pool.
+    async fn pragma<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        name: String,
+        value: Opt<Value<'js>>,
+        options: Opt<PragmaOptions>,
+    ) -> Result<Value<'js>> {
+        // The value can be omitted before the options
+        let (value, options) = match (value.0, options.0) {
+            (Some(value), None) if value.is_object() => (None, value.get()?),
+            (value, options) => (
+                value.filter(|value| !value.is_undefined()),
+                options.unwrap_or_default(),
+            ),
+        };
+        pragma::pragma(
+            &ctx,
+            &self.pool,
+            &self.registry,
+            &name,
+            value,
+            options,
+            self.options.get(),
+        )
+        .await
+    }
+
     async fn prepare(&self, ctx: Ctx<'_>, sql: String) -> Result<Statement> {
-        let stmt = sqlx::Statement::to_owned(&self.pool.prepare(&sql).await.or_throw(&ctx)?);
-        Ok(Statement::new(stmt, self.pool.clone()))
+        Statement::prepare(
+            &ctx,
+            Connection::Pool(self.pool.clone()),
+            &sql,
+            self.options.get(),
+        )
+        .await
+    }
+
+    async fn begin(&self, ctx: Ctx<'_>) -> Result<Transaction> {
+        Transaction::start(&ctx, &self.pool, self.options.get()).await
+    }
+
+    async fn transaction<'js>(&self, ctx: Ctx<'js>, callback: Function<'js>) -> Result<Value<'js>> {
+        let tx = Transaction::start(&ctx, &self.pool, self.options.get()).await?;
+        tx.run(ctx, callback).await
+    }
+
+    /// Applies the pending migrations in a single transaction, or reverts
+    /// the applied ones down to `to`.
+    async fn migrate<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        migrations: Vec<Migration<'js>>,
+        options: Opt<MigrateOptions>,
+    ) -> Result<Object<'js>> {
+        migrate::migrate(
+            &ctx,
+            &self.pool,
+            migrations,
+            options.0.unwrap_or_default(),
+            self.options.get(),
+        )
+        .await
+    }
+
+    async fn function<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        name: String,
+        options: Value<'js>,
+        callback: Opt<Function<'js>>,
+    ) -> Result<()> {
+        let (options, callback) =
+            function::parse_arguments::<FunctionOptions>(&ctx, options, callback.0)?;
+        let arity = options.arity(&ctx, &callback, 0)?;
+        let id = self.dispatcher.next_id();
+        let caller = self.dispatcher.caller(&ctx);
+        let install = function::installer(&ctx, name, arity, options.flags(), id, caller)?;
+
+        let mut conn = self.pool.acquire().await.or_throw_sqlite(&ctx)?;
+        self.registry
+            .add(&mut conn, install)
+            .await
+            .map_err(|err| err.context("Unable to register function").throw(&ctx))?;
+        self.dispatcher
+            .register(&ctx, id, Callback::Function(callback));
+        Ok(())
+    }
+
+    async fn aggregate<'js>(&self, ctx: Ctx<'js>, name: String, options: Value<'js>) -> Result<()> {
+        let (options, aggregate) = Aggregate::from_options(&ctx, options)?;
+        let arity = aggregate.arity(&ctx, &options)?;
+        let id = self.dispatcher.next_id();
+        let caller = self.dispatcher.caller(&ctx);
+        let install = aggregate::installer(
+            &ctx,
+            name,
+            arity,
+            options.flags(),
+            aggregate.is_window(),
+            id,
+            caller,
+        )?;
+
+        let mut conn = self.pool.acquire().await.or_throw_sqlite(&ctx)?;
+        self.registry
+            .add(&mut conn, install)
+            .await
+            .map_err(|err| err.context("Unable to register aggregate").throw(&ctx))?;
+        self.dispatcher
+            .register(&ctx, id, Callback::Aggregate(aggregate));
+        Ok(())
+    }
+
+    /// Defines a collation comparing texts with `compare`, on every
+    /// connection of the pool.
+    async fn collation<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        name: String,
+        compare: Function<'js>,
+    ) -> Result<()> {
+        let id = self.dispatcher.next_id();
+        let caller = self.dispatcher.caller(&ctx);
+        let install = collation::installer(&ctx, name, id, caller)?;
+
+        let mut conn = self.pool.acquire().await.or_throw_sqlite(&ctx)?;
+        self.registry
+            .add(&mut conn, install)
+            .await
+            .map_err(|err| err.context("Unable to register collation").throw(&ctx))?;
+        self.dispatcher
+            .register(&ctx, id, Callback::Collation(compare));
+        Ok(())
+    }
+
+    /// Listens to the rows inserted, updated or deleted by any connection.
+    async fn on_update<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        listener: Function<'js>,
+    ) -> Result<Function<'js>> {
+        self.listen(&ctx, Hook::Update, listener).await
+    }
+
+    /// Listens to the transactions committed by any connection.
+    async fn on_commit<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        listener: Function<'js>,
+    ) -> Result<Function<'js>> {
+        self.listen(&ctx, Hook::Commit, listener).await
+    }
+
+    /// Listens to the transactions rolled back by any connection.
+    async fn on_rollback<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        listener: Function<'js>,
+    ) -> Result<Function<'js>> {
+        self.listen(&ctx, Hook::Rollback, listener).await
+    }
+
+    async fn backup<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        destination: String,
+        options: Opt<BackupOptions<'js>>,
+    ) -> Result<Object<'js>> {
+        backup::backup(&ctx, &self.pool, destination, options.0.unwrap_or_default()).await
+    }
+
+    async fn serialize<'js>(&self, ctx: Ctx<'js>) -> Result<TypedArray<'js, u8>> {
+        serialize::serialize(&ctx, &self.pool).await
+    }
+
+    async fn deserialize<'js>(&self, ctx: Ctx<'js>, data: TypedArray<'js, u8>) -> Result<()> {
+        let data = CVec::from_array(data)?;
+        serialize::deserialize(&ctx, &self.pool, data.as_slice()).await
+    }
+
+    /// Opens a handle reading and writing a blob in place, on a dedicated
+    /// connection of the pool.
+    async fn open_blob<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        table: String,
+        column: String,
+        rowid: Value<'js>,
+        options: Opt<BlobOptions>,
+    ) -> Result<Blob> {
+        Blob::open(
+            &ctx,
+            &self.pool,
+            &table,
+            &column,
+            rowid,
+            options.0.unwrap_or_default(),
+        )
+        .await
+    }
+
+    /// Records the changes made through a dedicated connection of the pool.
+    async fn session<'js>(&self, ctx: Ctx<'js>, options: Opt<Value<'js>>) -> Result<Value<'js>> {
+        #[cfg(feature = "session")]
+        {
+            let options = match options.0 {
+                Some(options) => options.get()?,
+                None => SessionOptions::default(),
+            };
+            let session = Session::start(&ctx, &self.pool, options, self.options.get()).await?;
+            Ok(Class::instance(ctx, session)?.into_value())
+        }
+        #[cfg(not(feature = "session"))]
+        {
+            let _ = options;
+            Err(session_disabled(&ctx))
+        }
+    }
+
+    /// Applies a changeset or patchset, returns `false` if it was aborted.
+    async fn apply_changeset<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        changeset: TypedArray<'js, u8>,
+        options: Opt<Value<'js>>,
+    ) -> Result<bool> {
+        #[cfg(feature = "session")]
+        {
+            let options = match options.0 {
+                Some(options) => options.get()?,
+                None => ApplyOptions::default(),
+            };
+            let changeset = CVec::from_array(changeset)?.as_slice().to_vec();
+            session::apply(&ctx, &self.pool, &self.dispatcher, changeset, options).await
+        }
+        #[cfg(not(feature = "session"))]
+        {
+            let _ = (changeset, options);
+            Err(session_disabled(&ctx))
+        }
+    }
+
+    /// Loads an extension on every connection of the pool.
+    async fn load_extension<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        path: String,
+        entry_point: Opt<String>,
+    ) -> Result<()> {
+        #[cfg(feature = "sqlite-extensions")]
+        {
+            if !self.allow_extension {
+                return Err(rquickjs::Exception::throw_message(
+                    &ctx,
+                    "Loading extensions is not allowed, open the database with allowExtension: true",
+                ));
+            }
+            let install = extension::installer(&ctx, path, entry_point.0)?;
+            let mut conn = self.pool.acquire().await.or_throw_sqlite(&ctx)?;
+            self.registry
+                .add(&mut conn, install)
+                .await
+                .map_err(|err| err.context("Unable to load extension").throw(&ctx))
+        }
+        #[cfg(not(feature = "sqlite-extensions"))]
+        {
+            let _ = (path, entry_point, self.allow_extension);
+            Err(rquickjs::Exception::throw_message(
+                &ctx,
+                "Extensions are not available, rquickjs-extra-sqlite must be built with the sqlite-extensions feature",
+            ))
+        }
+    }
+
+    /// Toggles reading integers as `BigInt` for the statements prepared afterwards.
+    fn safe_integers<'js>(this: This<Class<'js, Self>>, toggle: Opt<bool>) -> Class<'js, Self> {
+        {
+            let db = this.0.borrow();
+            let mut options = db.options.get();
+            options.safe_integers = toggle.0.unwrap_or(true);
+            db.options.set(options);
+        }
+        this.0
     }
 
     async fn close(&mut self) -> Result<()> {
         self.pool.close().await;
+        self.dispatcher.close();
         Ok(())
     }
 }
@@ -110,4 +487,608 @@ mod tests {
         })
         .await;
     }
+
+    #[tokio::test]
+    async fn test_database_function() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.function("normalize", { deterministic: true }, (s) => s.trim().toLowerCase());
+                            await db.function("total", { varargs: true }, (...values) => values.reduce((a, b) => a + b, 0));
+                            await db.function("fail", () => { throw new Error("boom"); });
+
+                            const row = await db.prepare("SELECT normalize('  FOO ') AS name, total(1, 2, 3) AS total;").then((s) => s.get());
+                            let error;
+                            try {
+                                await db.prepare("SELECT fail();").then((s) => s.get());
+                            } catch (e) {
+                                error = e.message;
+                            }
+                            let invalid;
+                            try {
+                                // SQLite rejects names longer than 255 bytes
+                                await db.function("f".repeat(300), () => 1);
+                            } catch (e) {
+                                invalid = `${e.name} ${e.code} ${e.message}`;
+                            }
+                            await db.close();
+                            return [row.name, row.total, invalid, error].join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert!(
+                    result.starts_with(
+                        "foo,6,SqliteError SQLITE_MISUSE Unable to register function: "
+                    ),
+                    "{result}"
+                );
+                assert!(result.contains("boom"), "{result}");
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_database_collation() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true, maxConnections: 1 });
+                            await db.collation("numeric", (a, b) => {
+                                const [x, y] = [a, b].map((s) => parseInt(s.slice(4), 10));
+                                return x - y;
+                            });
+                            await db.collation("fail", () => { throw new Error("boom"); });
+                            await db.exec("CREATE TABLE test (name TEXT COLLATE numeric)");
+                            await db.exec("CREATE INDEX test_name ON test (name)");
+                            await db.exec("INSERT INTO test VALUES ('file10'), ('file2'), ('file1')");
+
+                            const names = await db.prepare("SELECT name FROM test ORDER BY name").then((s) => s.pluck().all());
+                            let failed;
+                            try {
+                                await db.prepare("SELECT name FROM test ORDER BY name COLLATE fail").then((s) => s.all());
+                            } catch (e) {
+                                failed = e.message;
+                            }
+                            const after = await db.prepare("SELECT count(*) FROM test").then((s) => s.pluck().get());
+                            let error;
+                            try {
+                                await db.collation("bad\0name", () => 0);
+                            } catch (e) {
+                                error = e.name;
+                            }
+                            await db.close();
+                            return [names.join(" "), failed, after, error].join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(result, "file1 file2 file10,boom,3,TypeError");
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_database_load_extension() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        const fails = async (f) => {
+                            try {
+                                await f();
+                                return "ok";
+                            } catch (e) {
+                                return e.message;
+                            }
+                        };
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            const denied = await fails(() => db.loadExtension("./missing"));
+                            const allowed = await fails(async () => {
+                                const db = await open({ inMemory: true, allowExtension: true });
+                                await db.loadExtension("./missing", "sqlite3_missing_init");
+                            });
+                            const sql = await fails(() => db.exec("SELECT load_extension('./missing')"));
+                            return [denied, allowed, sql].join("|");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                let [denied, allowed, sql] = result.split('|').collect::<Vec<_>>()[..] else {
+                    panic!("{result}");
+                };
+                if cfg!(feature = "sqlite-extensions") {
+                    assert!(denied.contains("allowExtension: true"), "{denied}");
+                    assert!(allowed.starts_with("Unable to load extension"), "{allowed}");
+                } else {
+                    assert!(denied.contains("sqlite-extensions feature"), "{denied}");
+                    assert!(allowed.contains("'allowExtension'"), "{allowed}");
+                }
+                assert!(sql.contains("not authorized"), "{sql}");
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_database_aggregate() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, value INTEGER);");
+                            await db.exec("INSERT INTO test (value) VALUES (1), (2), (3), (4);");
+                            await db.aggregate("product", {
+                                start: 1,
+                                step: (total, value) => total * value,
+                            });
+                            await db.aggregate("concat", {
+                                start: () => [],
+                                step: (values, value) => { values.push(value); },
+                                result: (values) => values.join("-"),
+                            });
+                            await db.aggregate("window_sum", {
+                                start: 0,
+                                step: (total, value) => total + value,
+                                inverse: (total, value) => total - value,
+                            });
+                            await db.aggregate("fail", {
+                                step: (total, value) => { throw new Error("boom"); },
+                            });
+
+                            const row = await db.prepare("SELECT product(value) AS product, concat(value) AS concat, product(value) FILTER (WHERE 0) AS empty FROM test;").then((s) => s.get());
+                            const rows = await db.prepare("SELECT window_sum(value) OVER (ORDER BY id ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) AS sum FROM test;").then((s) => s.all());
+                            let error;
+                            try {
+                                await db.prepare("SELECT fail(value) FROM test;").then((s) => s.get());
+                            } catch (e) {
+                                error = e.message;
+                            }
+                            await db.close();
+                            return [row.product, row.concat, row.empty, rows.map((r) => r.sum).join(" "), error].join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert!(result.starts_with("24,1-2-3-4,1,1 3 5 7,"), "{result}");
+                assert!(result.contains("boom"), "{result}");
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_database_backup() {
+        let path =
+            std::env::temp_dir().join(format!("rquickjs-sqlite-backup-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let destination = path.to_string_lossy().into_owned();
+
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test(path) {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, data BLOB)");
+                            await db.exec("WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 100) INSERT INTO test (data) SELECT randomblob(1000) FROM n");
+                            let steps = 0;
+                            const status = await db.backup(path, {
+                                pagesPerStep: 10,
+                                progress: ({ totalPages, remainingPages }) => {
+                                    if (remainingPages < totalPages) steps++;
+                                },
+                            });
+                            await db.close();
+
+                            const copy = await open({ filename: path, inMemory: false, wal: false });
+                            const row = await copy.prepare("SELECT count(*) AS count FROM test").then((s) => s.get());
+                            await copy.close();
+                            return [status.remainingPages, steps > 1, row.count].join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, (destination,)).await;
+                assert_eq!(result, "0,true,100");
+            })
+        })
+        .await;
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_database_serialize() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+                            await db.exec("INSERT INTO test (name) VALUES ('foo'), ('bar')");
+                            const data = await db.serialize();
+                            await db.close();
+
+                            const copy = await open({ data, maxConnections: 2 });
+                            await copy.exec("INSERT INTO test (name) VALUES ('baz')");
+                            const count = async (db) => db.prepare("SELECT count(*) FROM test").then((s) => s.pluck().get());
+                            const counts = await Promise.all([count(copy), count(copy)]);
+
+                            const other = await open({ inMemory: true });
+                            await other.deserialize(await copy.serialize());
+                            counts.push(await count(other));
+                            await copy.close();
+                            await other.close();
+                            return [data instanceof Uint8Array, ...counts].join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(result, "true,3,3,3");
+            })
+        })
+        .await;
+    }
+    #[tokio::test]
+    async fn test_database_hooks() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true, maxConnections: 2 });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+                            const events = [];
+                            const offUpdate = await db.onUpdate((op, database, table, rowid) => {
+                                events.push([op, database, table, rowid].join(" "));
+                            });
+                            await db.onCommit(() => events.push("commit"));
+                            await db.onRollback(() => events.push("rollback"));
+
+                            await db.exec("INSERT INTO test (name) VALUES ('foo')");
+                            await db.exec("UPDATE test SET name = 'bar' WHERE id = 1");
+                            await db.transaction(async (tx) => {
+                                await tx.exec("DELETE FROM test WHERE id = 1");
+                            });
+                            try {
+                                await db.transaction(async (tx) => {
+                                    await tx.exec("INSERT INTO test (name) VALUES ('baz')");
+                                    throw new Error("abort");
+                                });
+                            } catch {}
+                            // Rowids that do not fit in a number are delivered as a BigInt
+                            await db.exec("INSERT INTO test (id, name) VALUES (9007199254740993, 'big')");
+                            const flush = () => db.prepare("SELECT 1").then((s) => s.get());
+                            await flush();
+                            offUpdate();
+                            await db.exec("INSERT INTO test (name) VALUES ('qux')");
+                            await flush();
+                            await db.close();
+                            return events.join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    "insert main test 1,commit,update main test 1,commit,delete main test 1,commit,insert main test 1,rollback,insert main test 9007199254740993,commit,commit"
+                );
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_database_errors() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open, SqliteError } from "sqlite";
+
+                        const error = async (f) => {
+                            try {
+                                await f();
+                            } catch (e) {
+                                return e;
+                            }
+                        };
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT UNIQUE)");
+                            await db.exec("INSERT INTO test (name) VALUES ('a')");
+
+                            const insert = await db.prepare("INSERT INTO test (name) VALUES (?)");
+                            const e = await error(() => insert.run("a"));
+                            const syntax = await error(() => db.prepare("SELEC 1"));
+                            return [
+                                e instanceof SqliteError,
+                                e instanceof Error,
+                                e.name,
+                                e.code,
+                                e.errcode,
+                                e.extendedCode,
+                                e.sql,
+                                e.message,
+                                typeof e.stack,
+                                syntax.code,
+                                syntax.sql,
+                            ].join("|");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    "true|true|SqliteError|SQLITE_CONSTRAINT_UNIQUE|19|2067|INSERT INTO test (name) VALUES (?)|UNIQUE constraint failed: test.name|string|SQLITE_ERROR|SELEC 1"
+                );
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_database_pragma() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true, maxConnections: 2 });
+                            const results = [];
+                            results.push(await db.pragma("cache_size", -1234, { simple: true }));
+
+                            // Both connections of the pool are pinned by a transaction
+                            const first = await db.begin();
+                            const second = await db.begin();
+                            for (const tx of [first, second]) {
+                                const size = await tx.prepare("PRAGMA cache_size").then((s) => s.pluck().get());
+                                results.push(size);
+                                await tx.rollback();
+                            }
+
+                            await db.pragma("user_version", 7);
+                            results.push(await db.pragma("user_version", { simple: true }));
+                            results.push(JSON.stringify(await db.pragma("main.user_version")));
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+                            const columns = await db.pragma("table_info", "test");
+                            results.push(columns.map((column) => column.name).join(" "));
+                            try {
+                                await db.pragma("cache_size; DROP TABLE test");
+                            } catch (e) {
+                                results.push(e.name);
+                            }
+                            return results.join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    r#",-1234,-1234,7,[{"user_version":7}],id name,TypeError"#
+                );
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_database_migrate() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        const migrations = [
+                            { id: 1, up: "CREATE TABLE users (id INTEGER PRIMARY KEY)", down: "DROP TABLE users" },
+                            {
+                                id: 2,
+                                up: async (tx) => {
+                                    await tx.exec("ALTER TABLE users ADD COLUMN name TEXT");
+                                    await tx.exec("INSERT INTO users (name) VALUES ('foo')");
+                                },
+                                down: "ALTER TABLE users DROP COLUMN name",
+                            },
+                        ];
+
+                        const fails = async (f) => {
+                            try {
+                                await f();
+                                return "ok";
+                            } catch (e) {
+                                return e.message;
+                            }
+                        };
+
+                        const summary = ({ from, to, migrations }) => `${from}->${to}[${migrations}]`;
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            const version = () => db.pragma("user_version", { simple: true });
+                            const results = [];
+                            results.push(summary(await db.migrate(migrations)));
+                            results.push(summary(await db.migrate(migrations)));
+                            results.push(await db.prepare("SELECT name FROM users").then((s) => s.pluck().get()));
+
+                            // A failing migration leaves the database untouched
+                            const broken = [...migrations, { id: 3, up: "CREATE TABLE posts (id); SELECT * FROM missing" }];
+                            results.push(await fails(() => db.migrate(broken)));
+                            results.push(await version());
+                            results.push(await db.pragma("table_info", "posts").then((rows) => rows.length));
+
+                            results.push(summary(await db.migrate(migrations, { to: 1 })));
+                            results.push(JSON.stringify(await db.prepare("SELECT * FROM users").then((s) => s.all())));
+                            results.push(summary(await db.migrate(migrations)));
+
+                            results.push(await fails(() => db.migrate([migrations[0], { id: 5, up: "" }, migrations[1]])));
+                            results.push(await fails(() => db.migrate([migrations[0]])));
+                            results.push(await fails(() => db.migrate([migrations[0], { id: 2, up: "" }], { to: 0 })));
+
+                            // Refuse histories that do not match the migrations
+                            const other = await open({ inMemory: true });
+                            await other.migrate([{ id: 1, up: "" }, { id: 3, up: "" }]);
+                            results.push(await fails(() => other.migrate([{ id: 1, up: "" }, { id: 2, up: "" }, { id: 3, up: "" }])));
+                            await other.pragma("user_version", 9);
+                            results.push(await fails(() => other.migrate([{ id: 1, up: "" }, { id: 3, up: "" }])));
+                            results.push(await fails(() => other.migrate([{ id: 1.5, up: "" }])));
+                            return results.join("\n");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    [
+                        "0->2[1,2]",
+                        "2->2[]",
+                        "foo",
+                        "no such table: missing",
+                        "2",
+                        "0",
+                        "2->1[2]",
+                        r#"[{"id":1}]"#,
+                        "1->2[2]",
+                        "Migration ids must be increasing, 2 follows 5",
+                        "Applied migration 2 is unknown",
+                        "Migration 2 has no 'down' and can not be reverted",
+                        "Migration 2 was added before the applied migration 3",
+                        "Database version 9 does not match the last applied migration 3",
+                        "Invalid migration id: expected a positive integer, it is stored in user_version",
+                    ]
+                    .join("\n")
+                );
+            })
+        })
+        .await;
+    }
 }
@@ -0,0 +1,305 @@
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_int};
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+
+use libsqlite3_sys::{
+    SQLITE_DBCONFIG_DQS_DDL, SQLITE_DBCONFIG_DQS_DML, SQLITE_DBCONFIG_ENABLE_FKEY, SQLITE_OK,
+    SQLITE_OPEN_CREATE, SQLITE_OPEN_READONLY, SQLITE_OPEN_READWRITE, sqlite3, sqlite3_busy_timeout,
+    sqlite3_db_config, sqlite3_db_filename, sqlite3_exec, sqlite3_get_autocommit,
+};
+use rquickjs::function::Opt;
+use rquickjs::{Ctx, Exception, FromJs, JsLifetime, Object, Result, Value, class::Trace};
+
+use super::error::SqliteError;
+use super::raw::RawConnection;
+use super::statement_sync::StatementSync;
+
+/// The connection of a `DatabaseSync`, shared with its statements so they
+/// fail once it is closed.
+#[derive(Default)]
+pub struct SyncConnection(RefCell<Option<RawConnection>>);
+
+impl SyncConnection {
+    pub fn handle(&self, ctx: &Ctx<'_>) -> Result<NonNull<sqlite3>> {
+        self.0
+            .borrow()
+            .as_ref()
+            .map(RawConnection::as_raw_handle)
+            .ok_or_else(|| Exception::throw_message(ctx, "Database is not open"))
+    }
+
+    fn is_open(&self) -> bool {
+        self.0.borrow().is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DatabaseSyncOptions {
+    pub open: bool,
+    pub read_only: bool,
+    pub enable_foreign_key_constraints: bool,
+    pub enable_double_quoted_string_literals: bool,
+    /// Busy timeout in milliseconds.
+    pub timeout: c_int,
+}
+
+impl Default for DatabaseSyncOptions {
+    fn default() -> Self {
+        Self {
+            open: true,
+            read_only: false,
+            enable_foreign_key_constraints: true,
+            enable_double_quoted_string_literals: false,
+            timeout: 0,
+        }
+    }
+}
+
+impl<'js> FromJs<'js> for DatabaseSyncOptions {
+    fn from_js(ctx: &Ctx<'js>, value: Value<'js>) -> Result<Self> {
+        let default = DatabaseSyncOptions::default();
+        let obj = value.get::<Object<'js>>()?;
+        let open = obj.get::<_, Option<bool>>("open")?.unwrap_or(default.open);
+        let read_only = obj
+            .get::<_, Option<bool>>("readOnly")?
+            .unwrap_or(default.read_only);
+        let enable_foreign_key_constraints = obj
+            .get::<_, Option<bool>>("enableForeignKeyConstraints")?
+            .unwrap_or(default.enable_foreign_key_constraints);
+        let enable_double_quoted_string_literals = obj
+            .get::<_, Option<bool>>("enableDoubleQuotedStringLiterals")?
+            .unwrap_or(default.enable_double_quoted_string_literals);
+        let timeout = obj
+            .get::<_, Option<c_int>>("timeout")?
+            .unwrap_or(default.timeout);
+        if timeout < 0 {
+            return Err(Exception::throw_range(
+                ctx,
+                "timeout must be a non-negative number of milliseconds",
+            ));
+        }
+        Ok(Self {
+            open,
+            read_only,
+            enable_foreign_key_constraints,
+            enable_double_quoted_string_literals,
+            timeout,
+        })
+    }
+}
+
+/// A database backed by a single connection owned by the context, with a
+/// synchronous API compatible with `node:sqlite`.
+///
+/// It does not need an async runtime, but every call blocks the context
+/// until SQLite is done.
+#[derive(Trace, JsLifetime)]
+#[rquickjs::class]
+pub struct DatabaseSync {
+    #[qjs(skip_trace)]
+    path: CString,
+    #[qjs(skip_trace)]
+    options: DatabaseSyncOptions,
+    #[qjs(skip_trace)]
+    connection: RefCell<Rc<SyncConnection>>,
+}
+
+impl DatabaseSync {
+    fn connect(&self, ctx: &Ctx<'_>) -> Result<RawConnection> {
+        let flags = if self.options.read_only {
+            SQLITE_OPEN_READONLY
+        } else {
+            SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE
+        };
+        let conn = RawConnection::open(&self.path, flags)
+            .map_err(|err| err.context("Unable to open database").throw(ctx))?;
+        let db = conn.as_raw_handle().as_ptr();
+        // SAFETY: The connection is not shared yet.
+        unsafe {
+            sqlite3_busy_timeout(db, self.options.timeout);
+            for (op, enable) in [
+                (
+                    SQLITE_DBCONFIG_ENABLE_FKEY,
+                    self.options.enable_foreign_key_constraints,
+                ),
+                (
+                    SQLITE_DBCONFIG_DQS_DML,
+                    self.options.enable_double_quoted_string_literals,
+                ),
+                (
+                    SQLITE_DBCONFIG_DQS_DDL,
+                    self.options.enable_double_quoted_string_literals,
+                ),
+            ] {
+                sqlite3_db_config(db, op, enable as c_int, ptr::null_mut::<c_int>());
+            }
+        }
+        Ok(conn)
+    }
+
+    fn connection(&self) -> Rc<SyncConnection> {
+        self.connection.borrow().clone()
+    }
+}
+
+#[rquickjs::methods(rename_all = "camelCase")]
+impl DatabaseSync {
+    #[qjs(constructor)]
+    pub fn new(ctx: Ctx<'_>, path: String, options: Opt<DatabaseSyncOptions>) -> Result<Self> {
+        let path = CString::new(path)
+            .map_err(|_| Exception::throw_type(&ctx, "Path must not contain NUL characters"))?;
+        let database = Self {
+            path,
+            options: options.0.unwrap_or_default(),
+            connection: RefCell::default(),
+        };
+        if database.options.open {
+            database.open(ctx)?;
+        }
+        Ok(database)
+    }
+
+    /// Opens the database, when it was created with `open: false` or closed.
+    pub fn open(&self, ctx: Ctx<'_>) -> Result<()> {
+        if self.connection().is_open() {
+            return Err(Exception::throw_message(&ctx, "Database is already open"));
+        }
+        let conn = self.connect(&ctx)?;
+        // Statements of a previous connection stay closed
+        self.connection
+            .replace(Rc::new(SyncConnection(RefCell::new(Some(conn)))));
+        Ok(())
+    }
+
+    /// Closes the database, its statements can no longer be used.
+    pub fn close(&self, ctx: Ctx<'_>) -> Result<()> {
+        let connection = self.connection();
+        if connection.0.borrow_mut().take().is_none() {
+            return Err(Exception::throw_message(&ctx, "Database is not open"));
+        }
+        Ok(())
+    }
+
+    #[qjs(get)]
+    fn is_open(&self) -> bool {
+        self.connection().is_open()
+    }
+
+    #[qjs(get)]
+    fn is_transaction(&self, ctx: Ctx<'_>) -> Result<bool> {
+        let db = self.connection().handle(&ctx)?;
+        // SAFETY: The connection is only used by this context.
+        Ok(unsafe { sqlite3_get_autocommit(db.as_ptr()) } == 0)
+    }
+
+    /// The filename of the database attached as `name`, `main` by default.
+    /// Returns `null` for in-memory databases.
+    pub fn location(&self, ctx: Ctx<'_>, name: Opt<String>) -> Result<Option<String>> {
+        let db = self.connection().handle(&ctx)?;
+        let name = CString::new(name.0.as_deref().unwrap_or("main"))
+            .map_err(|_| Exception::throw_type(&ctx, "Name must not contain NUL characters"))?;
+        // SAFETY: The connection is only used by this context, the filename is
+        // copied before it is used again.
+        let filename = unsafe { sqlite3_db_filename(db.as_ptr(), name.as_ptr()) };
+        if filename.is_null() {
+            return Ok(None);
+        }
+        // SAFETY: The filename is a valid C string owned by SQLite.
+        let filename = unsafe { CStr::from_ptr(filename) }.to_string_lossy();
+        Ok((!filename.is_empty()).then(|| filename.into_owned()))
+    }
+
+    /// Executes one or more SQL statements without returning any results.
+    pub fn exec(&self, ctx: Ctx<'_>, sql: String) -> Result<()> {
+        let db = self.connection().handle(&ctx)?;
+        let c_sql = CString::new(sql.as_str())
+            .map_err(|_| Exception::throw_type(&ctx, "SQL must not contain NUL characters"))?;
+        // SAFETY: The connection is only used by this context.
+        let code = unsafe {
+            sqlite3_exec(
+                db.as_ptr(),
+                c_sql.as_ptr(),
+                None,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        };
+        if code != SQLITE_OK {
+            return Err(SqliteError::from_handle(db).with_sql(sql).throw(&ctx));
+        }
+        Ok(())
+    }
+
+    pub fn prepare(&self, ctx: Ctx<'_>, sql: String) -> Result<StatementSync> {
+        StatementSync::prepare(&ctx, self.connection(), sql)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rquickjs::{CatchResultExt, Class};
+    use rquickjs_extra_test::test_with;
+
+    use super::*;
+
+    #[test]
+    fn test_database_sync() {
+        test_with(|ctx| {
+            Class::<DatabaseSync>::define(&ctx.globals()).unwrap();
+            Class::<StatementSync>::define(&ctx.globals()).unwrap();
+            let result = ctx
+                .eval::<String, _>(
+                    r#"
+                const db = new DatabaseSync(":memory:");
+                db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+                const insert = db.prepare("INSERT INTO test (name) VALUES (?)");
+                const first = insert.run("a");
+                insert.run("b");
+                const named = db.prepare("SELECT * FROM test WHERE id > ? AND name != $name");
+                const rows = named.all({ $name: "c" }, 0);
+                const names = [];
+                for (const row of db.prepare("SELECT name FROM test ORDER BY id DESC").iterate()) {
+                    names.push(row.name);
+                }
+                const missing = db.prepare("SELECT * FROM test WHERE id = ?").get(3);
+                let error;
+                try {
+                    db.exec("INSERT INTO test (id) VALUES (1)");
+                } catch (e) {
+                    error = e.code;
+                }
+                db.exec("BEGIN");
+                const inTransaction = db.isTransaction;
+                db.exec("ROLLBACK");
+                const stmt = db.prepare("SELECT 1");
+                db.close();
+                let closed;
+                try {
+                    stmt.get();
+                } catch (e) {
+                    closed = e.message;
+                }
+                [
+                    first.changes,
+                    first.lastInsertRowid,
+                    rows.map((row) => row.name).join(""),
+                    names.join(""),
+                    missing,
+                    error,
+                    inTransaction,
+                    db.isOpen,
+                    closed,
+                    stmt instanceof StatementSync,
+                ].join()
+            "#,
+                )
+                .catch(&ctx)
+                .unwrap();
+            assert_eq!(
+                result,
+                "1,1,ab,ba,,SQLITE_CONSTRAINT_PRIMARYKEY,true,false,Database is not open,true"
+            );
+        })
+    }
+}
@@ -0,0 +1,316 @@
+use std::ffi::c_int;
+use std::ptr::NonNull;
+
+use libsqlite3_sys::{sqlite3, sqlite3_extended_errcode};
+use rquickjs::{
+    Class, Ctx, Exception, JsLifetime, Object, Result, class::Trace, function::Constructor,
+};
+
+use super::raw::{error_message, error_string};
+
+macro_rules! codes {
+    ($($code:ident),* $(,)?) => {
+        /// The name of a primary or extended result code.
+        fn code_name(code: c_int) -> Option<&'static str> {
+            match code {
+                $(libsqlite3_sys::$code => Some(stringify!($code)),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+codes!(
+    SQLITE_ERROR,
+    SQLITE_INTERNAL,
+    SQLITE_PERM,
+    SQLITE_ABORT,
+    SQLITE_BUSY,
+    SQLITE_LOCKED,
+    SQLITE_NOMEM,
+    SQLITE_READONLY,
+    SQLITE_INTERRUPT,
+    SQLITE_IOERR,
+    SQLITE_CORRUPT,
+    SQLITE_NOTFOUND,
+    SQLITE_FULL,
+    SQLITE_CANTOPEN,
+    SQLITE_PROTOCOL,
+    SQLITE_EMPTY,
+    SQLITE_SCHEMA,
+    SQLITE_TOOBIG,
+    SQLITE_CONSTRAINT,
+    SQLITE_MISMATCH,
+    SQLITE_MISUSE,
+    SQLITE_NOLFS,
+    SQLITE_AUTH,
+    SQLITE_FORMAT,
+    SQLITE_RANGE,
+    SQLITE_NOTADB,
+    SQLITE_NOTICE,
+    SQLITE_WARNING,
+    SQLITE_ERROR_MISSING_COLLSEQ,
+    SQLITE_ERROR_RETRY,
+    SQLITE_ERROR_SNAPSHOT,
+    SQLITE_IOERR_READ,
+    SQLITE_IOERR_SHORT_READ,
+    SQLITE_IOERR_WRITE,
+    SQLITE_IOERR_FSYNC,
+    SQLITE_IOERR_DIR_FSYNC,
+    SQLITE_IOERR_TRUNCATE,
+    SQLITE_IOERR_FSTAT,
+    SQLITE_IOERR_UNLOCK,
+    SQLITE_IOERR_RDLOCK,
+    SQLITE_IOERR_DELETE,
+    SQLITE_IOERR_BLOCKED,
+    SQLITE_IOERR_NOMEM,
+    SQLITE_IOERR_ACCESS,
+    SQLITE_IOERR_CHECKRESERVEDLOCK,
+    SQLITE_IOERR_LOCK,
+    SQLITE_IOERR_CLOSE,
+    SQLITE_IOERR_DIR_CLOSE,
+    SQLITE_IOERR_SHMOPEN,
+    SQLITE_IOERR_SHMSIZE,
+    SQLITE_IOERR_SHMLOCK,
+    SQLITE_IOERR_SHMMAP,
+    SQLITE_IOERR_SEEK,
+    SQLITE_IOERR_DELETE_NOENT,
+    SQLITE_IOERR_MMAP,
+    SQLITE_IOERR_GETTEMPPATH,
+    SQLITE_IOERR_CONVPATH,
+    SQLITE_IOERR_VNODE,
+    SQLITE_IOERR_AUTH,
+    SQLITE_IOERR_BEGIN_ATOMIC,
+    SQLITE_IOERR_COMMIT_ATOMIC,
+    SQLITE_IOERR_ROLLBACK_ATOMIC,
+    SQLITE_IOERR_DATA,
+    SQLITE_IOERR_CORRUPTFS,
+    SQLITE_IOERR_IN_PAGE,
+    SQLITE_LOCKED_SHAREDCACHE,
+    SQLITE_LOCKED_VTAB,
+    SQLITE_BUSY_RECOVERY,
+    SQLITE_BUSY_SNAPSHOT,
+    SQLITE_BUSY_TIMEOUT,
+    SQLITE_CANTOPEN_NOTEMPDIR,
+    SQLITE_CANTOPEN_ISDIR,
+    SQLITE_CANTOPEN_FULLPATH,
+    SQLITE_CANTOPEN_CONVPATH,
+    SQLITE_CANTOPEN_DIRTYWAL,
+    SQLITE_CANTOPEN_SYMLINK,
+    SQLITE_CORRUPT_VTAB,
+    SQLITE_CORRUPT_SEQUENCE,
+    SQLITE_CORRUPT_INDEX,
+    SQLITE_READONLY_RECOVERY,
+    SQLITE_READONLY_CANTLOCK,
+    SQLITE_READONLY_ROLLBACK,
+    SQLITE_READONLY_DBMOVED,
+    SQLITE_READONLY_CANTINIT,
+    SQLITE_READONLY_DIRECTORY,
+    SQLITE_ABORT_ROLLBACK,
+    SQLITE_CONSTRAINT_CHECK,
+    SQLITE_CONSTRAINT_COMMITHOOK,
+    SQLITE_CONSTRAINT_FOREIGNKEY,
+    SQLITE_CONSTRAINT_FUNCTION,
+    SQLITE_CONSTRAINT_NOTNULL,
+    SQLITE_CONSTRAINT_PRIMARYKEY,
+    SQLITE_CONSTRAINT_TRIGGER,
+    SQLITE_CONSTRAINT_UNIQUE,
+    SQLITE_CONSTRAINT_VTAB,
+    SQLITE_CONSTRAINT_ROWID,
+    SQLITE_CONSTRAINT_PINNED,
+    SQLITE_CONSTRAINT_DATATYPE,
+    SQLITE_NOTICE_RECOVER_WAL,
+    SQLITE_NOTICE_RECOVER_ROLLBACK,
+    SQLITE_NOTICE_RBU,
+    SQLITE_WARNING_AUTOINDEX,
+    SQLITE_AUTH_USER,
+);
+
+/// An error reported by SQLite.
+///
+/// Instances are only created from Rust, their prototype inherits from
+/// `Error.prototype` so they behave like native errors in JS.
+#[derive(Debug, Clone, Trace, JsLifetime)]
+#[rquickjs::class]
+pub struct SqliteError {
+    #[qjs(get, enumerable)]
+    message: String,
+    /// The name of the extended result code, like `SQLITE_CONSTRAINT_UNIQUE`.
+    #[qjs(get, enumerable)]
+    code: String,
+    /// The primary result code.
+    #[qjs(get, enumerable)]
+    errcode: i32,
+    #[qjs(get, enumerable, rename = "extendedCode")]
+    extended_code: i32,
+    /// The SQL that failed, when known.
+    #[qjs(get, enumerable)]
+    sql: Option<String>,
+    #[qjs(get)]
+    stack: String,
+}
+
+impl SqliteError {
+    pub fn new(extended_code: c_int, message: impl Into<String>) -> Self {
+        let errcode = extended_code & 0xff;
+        let code = code_name(extended_code)
+            .or_else(|| code_name(errcode))
+            .unwrap_or("SQLITE_ERROR");
+        Self {
+            message: message.into(),
+            code: code.into(),
+            errcode,
+            extended_code,
+            sql: None,
+            stack: String::new(),
+        }
+    }
+
+    /// The last error of a connection.
+    pub fn from_handle(db: NonNull<sqlite3>) -> Self {
+        // SAFETY: The code is read right after the failed call on the connection.
+        let code = unsafe { sqlite3_extended_errcode(db.as_ptr()) };
+        Self::new(code, error_message(db))
+    }
+
+    /// An error with the description of a result code as message.
+    pub fn from_code(code: c_int) -> Self {
+        Self::new(code, error_string(code))
+    }
+
+    /// Converts an error returned by SQLite through sqlx, or by the
+    /// installers of a connection, other sqlx errors are not SQLite errors.
+    pub fn from_sqlx(err: &sqlx::Error) -> Option<Self> {
+        match err {
+            sqlx::Error::Database(err) => {
+                let code = err.code()?.parse().ok()?;
+                Some(Self::new(code, err.message()))
+            }
+            sqlx::Error::Configuration(err) => err.downcast_ref::<Self>().cloned(),
+            _ => None,
+        }
+    }
+
+    /// The primary result code.
+    pub fn primary_code(&self) -> c_int {
+        self.errcode
+    }
+
+    pub fn with_sql(mut self, sql: impl Into<String>) -> Self {
+        self.sql = Some(sql.into());
+        self
+    }
+
+    /// Prefixes the message with what was being done.
+    pub fn context(mut self, context: &str) -> Self {
+        self.message = [context, ": ", &self.message].concat();
+        self
+    }
+
+    pub fn throw(mut self, ctx: &Ctx<'_>) -> rquickjs::Error {
+        match self.instance(ctx) {
+            Ok(error) => ctx.throw(error.into_value()),
+            Err(err) => err,
+        }
+    }
+
+    /// Defines the class on `object`, inheriting from `Error`.
+    pub fn define(object: &Object<'_>) -> Result<()> {
+        Class::<Self>::define(object)?;
+        Self::inherit_error(object.ctx())
+    }
+
+    fn inherit_error(ctx: &Ctx<'_>) -> Result<()> {
+        if let Some(prototype) = Class::<Self>::prototype(ctx)? {
+            let error = ctx.globals().get::<_, Constructor>("Error")?;
+            let error_prototype = error.get::<_, Object>("prototype")?;
+            if prototype.get_prototype().as_ref() != Some(&error_prototype) {
+                prototype.set_prototype(Some(&error_prototype))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn instance<'js>(&mut self, ctx: &Ctx<'js>) -> Result<Class<'js, Self>> {
+        Self::inherit_error(ctx)?;
+        // Borrow the stack of a native error created at the same point
+        let native = Exception::from_message(ctx.clone(), &self.message)?;
+        self.stack = native.stack().unwrap_or_default();
+        Class::instance(ctx.clone(), self.clone())
+    }
+}
+
+impl std::fmt::Display for SqliteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for SqliteError {}
+
+#[rquickjs::methods(rename_all = "camelCase")]
+impl SqliteError {
+    /// Only exposed so `instanceof` works, errors are created by the module.
+    #[qjs(constructor)]
+    fn construct(ctx: Ctx<'_>) -> Result<Self> {
+        Err(Exception::throw_type(&ctx, "Illegal constructor"))
+    }
+
+    #[qjs(get)]
+    fn name(&self) -> &'static str {
+        "SqliteError"
+    }
+}
+
+/// Throws the errors of SQLite as `SqliteError`.
+pub trait ResultExt<T> {
+    fn or_throw_sqlite(self, ctx: &Ctx<'_>) -> Result<T>;
+
+    /// Like [`ResultExt::or_throw_sqlite`], recording the SQL that failed.
+    fn or_throw_sql(self, ctx: &Ctx<'_>, sql: &str) -> Result<T>;
+}
+
+impl<T> ResultExt<T> for std::result::Result<T, sqlx::Error> {
+    fn or_throw_sqlite(self, ctx: &Ctx<'_>) -> Result<T> {
+        self.map_err(|err| match SqliteError::from_sqlx(&err) {
+            Some(error) => error.throw(ctx),
+            None => Exception::throw_message(ctx, &err.to_string()),
+        })
+    }
+
+    fn or_throw_sql(self, ctx: &Ctx<'_>, sql: &str) -> Result<T> {
+        self.map_err(|err| match SqliteError::from_sqlx(&err) {
+            Some(error) => error.with_sql(sql).throw(ctx),
+            None => Exception::throw_message(ctx, &err.to_string()),
+        })
+    }
+}
+
+impl<T> ResultExt<T> for std::result::Result<T, SqliteError> {
+    fn or_throw_sqlite(self, ctx: &Ctx<'_>) -> Result<T> {
+        self.map_err(|err| err.throw(ctx))
+    }
+
+    fn or_throw_sql(self, ctx: &Ctx<'_>, sql: &str) -> Result<T> {
+        self.map_err(|err| err.with_sql(sql).throw(ctx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SqliteError;
+
+    #[test]
+    fn test_error_codes() {
+        let error = SqliteError::new(libsqlite3_sys::SQLITE_CONSTRAINT_UNIQUE, "");
+        assert_eq!(error.code, "SQLITE_CONSTRAINT_UNIQUE");
+        assert_eq!(error.errcode, libsqlite3_sys::SQLITE_CONSTRAINT);
+
+        let error = SqliteError::new(libsqlite3_sys::SQLITE_BUSY, "");
+        assert_eq!(error.code, "SQLITE_BUSY");
+
+        // Unknown extended codes fall back to their primary code
+        let error = SqliteError::new(libsqlite3_sys::SQLITE_IOERR | (99 << 8), "");
+        assert_eq!(error.code, "SQLITE_IOERR");
+    }
+}
@@ -0,0 +1,81 @@
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::ptr::{self, NonNull};
+
+use libsqlite3_sys::{
+    SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION, SQLITE_OK, sqlite3, sqlite3_db_config, sqlite3_free,
+    sqlite3_load_extension,
+};
+use rquickjs::{Ctx, Exception, Result};
+
+use super::error::SqliteError;
+
+/// Creates the installer loading an extension, from `path` with the default
+/// entry point unless `entry_point` is given.
+pub fn installer(
+    ctx: &Ctx<'_>,
+    path: String,
+    entry_point: Option<String>,
+) -> Result<impl Fn(NonNull<sqlite3>) -> std::result::Result<(), SqliteError> + Send + Sync + 'static>
+{
+    let path = CString::new(path).map_err(|_| {
+        Exception::throw_type(ctx, "Extension path must not contain NUL characters")
+    })?;
+    let entry_point = entry_point
+        .map(CString::new)
+        .transpose()
+        .map_err(|_| Exception::throw_type(ctx, "Entry point must not contain NUL characters"))?;
+    Ok(move |db: NonNull<sqlite3>| {
+        // SAFETY: The connection handle is locked by the caller.
+        unsafe { load(db, &path, entry_point.as_deref()) }
+    })
+}
+
+/// Loads an extension, only enabling extension loading while it runs so the
+/// `load_extension()` SQL function stays disabled.
+///
+/// # Safety
+/// `db` must be locked while the extension is loaded.
+unsafe fn load(
+    db: NonNull<sqlite3>,
+    path: &CStr,
+    entry_point: Option<&CStr>,
+) -> std::result::Result<(), SqliteError> {
+    unsafe {
+        let code = sqlite3_db_config(
+            db.as_ptr(),
+            SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION,
+            1 as c_int,
+            ptr::null_mut::<c_int>(),
+        );
+        if code != SQLITE_OK {
+            return Err(SqliteError::from_code(code));
+        }
+
+        let mut message: *mut c_char = ptr::null_mut();
+        let code = sqlite3_load_extension(
+            db.as_ptr(),
+            path.as_ptr(),
+            entry_point.map_or(ptr::null(), CStr::as_ptr),
+            &mut message,
+        );
+        let result = if code == SQLITE_OK {
+            Ok(())
+        } else if message.is_null() {
+            Err(SqliteError::from_code(code))
+        } else {
+            Err(SqliteError::new(
+                code,
+                CStr::from_ptr(message).to_string_lossy(),
+            ))
+        };
+        sqlite3_free(message as *mut _);
+
+        sqlite3_db_config(
+            db.as_ptr(),
+            SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION,
+            0 as c_int,
+            ptr::null_mut::<c_int>(),
+        );
+        result
+    }
+}
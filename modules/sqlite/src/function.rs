@@ -0,0 +1,201 @@
+use std::ffi::{CString, c_int, c_void};
+use std::ptr::NonNull;
+
+use libsqlite3_sys::{
+    SQLITE_DETERMINISTIC, SQLITE_DIRECTONLY, SQLITE_OK, SQLITE_UTF8, sqlite3, sqlite3_context,
+    sqlite3_create_function_v2, sqlite3_result_error, sqlite3_user_data, sqlite3_value,
+};
+use rquickjs::{Ctx, Exception, FromJs, Function, Object, Result, Value};
+
+use super::Argument;
+use super::callback::{Call, Caller, RawArgs, RawContext, error_message};
+use super::error::SqliteError;
+
+#[derive(Debug, Clone, Default)]
+pub struct FunctionOptions {
+    pub deterministic: bool,
+    pub varargs: bool,
+    pub direct_only: bool,
+}
+
+impl<'js> FromJs<'js> for FunctionOptions {
+    fn from_js(_ctx: &Ctx<'js>, value: Value<'js>) -> Result<Self> {
+        let default = FunctionOptions::default();
+        let obj = value.get::<Object<'js>>()?;
+        let deterministic = obj
+            .get::<_, Option<bool>>("deterministic")?
+            .unwrap_or(default.deterministic);
+        let varargs = obj
+            .get::<_, Option<bool>>("varargs")?
+            .unwrap_or(default.varargs);
+        let direct_only = obj
+            .get::<_, Option<bool>>("directOnly")?
+            .unwrap_or(default.direct_only);
+        Ok(Self {
+            deterministic,
+            varargs,
+            direct_only,
+        })
+    }
+}
+
+impl FunctionOptions {
+    pub fn flags(&self) -> c_int {
+        let mut flags = SQLITE_UTF8;
+        if self.deterministic {
+            flags |= SQLITE_DETERMINISTIC;
+        }
+        if self.direct_only {
+            flags |= SQLITE_DIRECTONLY;
+        }
+        flags
+    }
+
+    /// Number of SQL arguments of `callback`, ignoring its first `skip` parameters.
+    pub fn arity(&self, ctx: &Ctx<'_>, callback: &Function<'_>, skip: c_int) -> Result<c_int> {
+        if self.varargs {
+            return Ok(-1);
+        }
+        let arity = (callback.get::<_, c_int>("length")? - skip).max(0);
+        if arity > 127 {
+            return Err(Exception::throw_range(
+                ctx,
+                "Functions can have at most 127 arguments, use the varargs option instead",
+            ));
+        }
+        Ok(arity)
+    }
+}
+
+/// Reads the arguments of `(name, options?, callback)` style methods.
+pub fn parse_arguments<'js, T: FromJs<'js> + Default>(
+    ctx: &Ctx<'js>,
+    options: Value<'js>,
+    callback: Option<Function<'js>>,
+) -> Result<(T, Function<'js>)> {
+    match callback {
+        Some(callback) => Ok((T::from_js(ctx, options)?, callback)),
+        None => Ok((T::default(), Function::from_js(ctx, options)?)),
+    }
+}
+
+struct FunctionHandle {
+    id: usize,
+    caller: Caller,
+}
+
+/// Creates the installer of a scalar function calling the JS callback `id`.
+pub fn installer(
+    ctx: &Ctx<'_>,
+    name: String,
+    arity: c_int,
+    flags: c_int,
+    id: usize,
+    caller: Caller,
+) -> Result<impl Fn(NonNull<sqlite3>) -> std::result::Result<(), SqliteError> + Send + Sync + 'static>
+{
+    let name = CString::new(name)
+        .map_err(|_| Exception::throw_type(ctx, "Function name must not contain NUL characters"))?;
+    Ok(move |db: NonNull<sqlite3>| {
+        let handle = Box::into_raw(Box::new(FunctionHandle {
+            id,
+            caller: caller.clone(),
+        }));
+        // SAFETY: The connection handle is locked by the caller. SQLite owns
+        // the handle from now on and frees it with `destroy_function`.
+        let code = unsafe {
+            sqlite3_create_function_v2(
+                db.as_ptr(),
+                name.as_ptr(),
+                arity,
+                flags,
+                handle as *mut c_void,
+                Some(call_function),
+                None,
+                None,
+                Some(destroy_function),
+            )
+        };
+        check(code)
+    })
+}
+
+pub fn check(code: c_int) -> std::result::Result<(), SqliteError> {
+    if code == SQLITE_OK {
+        return Ok(());
+    }
+    Err(SqliteError::from_code(code))
+}
+
+unsafe extern "C" fn call_function(
+    context: *mut sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) {
+    // SAFETY: The user data is the handle given to sqlite3_create_function_v2.
+    let handle = unsafe { &*(sqlite3_user_data(context) as *const FunctionHandle) };
+    let called = handle.caller.call(|done| Call::Function {
+        id: handle.id,
+        context: RawContext(context),
+        args: RawArgs(argv, argc),
+        done,
+    });
+    if called.is_none() {
+        unsafe { sqlite3_result_error(context, c"Database is closed".as_ptr(), -1) };
+    }
+}
+
+unsafe extern "C" fn destroy_function(data: *mut c_void) {
+    drop(unsafe { Box::from_raw(data as *mut FunctionHandle) });
+}
+
+/// Calls a scalar function on the JS context and sets its result.
+pub fn call<'js>(
+    ctx: &Ctx<'js>,
+    callback: Option<&Function<'js>>,
+    context: &RawContext,
+    args: &RawArgs,
+) {
+    let result = (|| {
+        let callback = callback.ok_or_else(|| Exception::throw_message(ctx, "Unknown function"))?;
+        let value = callback.call::<_, Value>((read_args(ctx, args)?,))?;
+        if value.is_promise() {
+            return Err(Exception::throw_type(
+                ctx,
+                "User-defined functions must be synchronous",
+            ));
+        }
+        let value = Argument::from_js(ctx, value)?;
+        // SAFETY: The worker thread is blocked until the call is answered.
+        unsafe { value.set_result(context.0) };
+        Ok(())
+    })();
+    if let Err(err) = result {
+        set_error(ctx, context, err);
+    }
+}
+
+pub fn read_args<'js>(
+    ctx: &Ctx<'js>,
+    args: &RawArgs,
+) -> Result<rquickjs::function::Rest<Value<'js>>> {
+    let mut values = Vec::with_capacity(args.as_slice().len());
+    for arg in args.as_slice() {
+        // SAFETY: The worker thread is blocked until the call is answered.
+        let value = unsafe { super::Value::try_from_raw(ctx, *arg)? };
+        values.push(rquickjs::IntoJs::into_js(value, ctx)?);
+    }
+    Ok(rquickjs::function::Rest(values))
+}
+
+pub fn set_error(ctx: &Ctx<'_>, context: &RawContext, err: rquickjs::Error) {
+    let message = error_message(ctx, err);
+    // SAFETY: The worker thread is blocked until the call is answered.
+    unsafe {
+        sqlite3_result_error(
+            context.0,
+            message.as_ptr() as *const _,
+            message.len() as c_int,
+        )
+    };
+}
@@ -0,0 +1,158 @@
+use std::ffi::{CStr, c_char, c_int, c_void};
+use std::ptr::NonNull;
+
+use libsqlite3_sys::{
+    SQLITE_DELETE, SQLITE_INSERT, SQLITE_UPDATE, sqlite3, sqlite3_commit_hook, sqlite3_int64,
+    sqlite3_rollback_hook, sqlite3_set_clientdata, sqlite3_update_hook,
+};
+use rquickjs::{
+    Ctx, Function, Result,
+    function::{Args, IntoArgs},
+};
+
+use super::callback::{Call, Caller};
+use super::error::SqliteError;
+use super::value::rowid_into_js;
+
+/// The change notifications of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hook {
+    Update,
+    Commit,
+    Rollback,
+}
+
+/// A change notification sent by a SQLite worker thread.
+#[derive(Debug)]
+pub enum Event {
+    Update {
+        operation: &'static str,
+        database: String,
+        table: String,
+        rowid: i64,
+    },
+    Commit,
+    Rollback,
+}
+
+impl Event {
+    pub fn hook(&self) -> Hook {
+        match self {
+            Event::Update { .. } => Hook::Update,
+            Event::Commit => Hook::Commit,
+            Event::Rollback => Hook::Rollback,
+        }
+    }
+
+    /// Queues a call of `listener` with the event on the JS job queue.
+    pub fn deliver<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        listener: &Function<'js>,
+        safe_integers: bool,
+    ) -> Result<()> {
+        // `Function::defer` overflows its inline arguments when the callee and
+        // `this` are added to four arguments, so they are allocated instead.
+        let mut args = Args::new_unsized(ctx.clone());
+        if let Event::Update {
+            operation,
+            database,
+            table,
+            rowid,
+        } = self
+        {
+            let rowid = rowid_into_js(ctx, *rowid, safe_integers)?;
+            (*operation, database.as_str(), table.as_str(), rowid).into_args(&mut args)?;
+        }
+        listener.defer_arg(args)
+    }
+}
+
+impl Hook {
+    /// The client data key holding the caller of the hook, which frees it
+    /// when the connection is closed.
+    fn key(self) -> &'static CStr {
+        match self {
+            Hook::Update => c"rquickjs_extra_sqlite_update_hook",
+            Hook::Commit => c"rquickjs_extra_sqlite_commit_hook",
+            Hook::Rollback => c"rquickjs_extra_sqlite_rollback_hook",
+        }
+    }
+
+    /// Creates the installer of the hook, sending its events to `caller`.
+    pub fn installer(
+        self,
+        caller: Caller,
+    ) -> impl Fn(NonNull<sqlite3>) -> std::result::Result<(), SqliteError> + Send + Sync + 'static
+    {
+        move |db: NonNull<sqlite3>| {
+            let data = Box::into_raw(Box::new(caller.clone())) as *mut c_void;
+            // SAFETY: The connection handle is locked by the registry, so the
+            // previous caller of the hook is not in use when it is replaced.
+            unsafe {
+                sqlite3_set_clientdata(db.as_ptr(), self.key().as_ptr(), data, Some(destroy));
+                match self {
+                    Hook::Update => {
+                        sqlite3_update_hook(db.as_ptr(), Some(update_hook), data);
+                    }
+                    Hook::Commit => {
+                        sqlite3_commit_hook(db.as_ptr(), Some(commit_hook), data);
+                    }
+                    Hook::Rollback => {
+                        sqlite3_rollback_hook(db.as_ptr(), Some(rollback_hook), data);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+unsafe fn caller<'a>(data: *mut c_void) -> &'a Caller {
+    unsafe { &*(data as *const Caller) }
+}
+
+unsafe fn text(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+unsafe extern "C" fn update_hook(
+    data: *mut c_void,
+    operation: c_int,
+    database: *const c_char,
+    table: *const c_char,
+    rowid: sqlite3_int64,
+) {
+    let operation = match operation {
+        SQLITE_INSERT => "insert",
+        SQLITE_UPDATE => "update",
+        SQLITE_DELETE => "delete",
+        _ => return,
+    };
+    let event = Event::Update {
+        operation,
+        database: unsafe { text(database) },
+        table: unsafe { text(table) },
+        rowid,
+    };
+    unsafe { caller(data) }.notify(Call::Event(event));
+}
+
+unsafe extern "C" fn commit_hook(data: *mut c_void) -> c_int {
+    unsafe { caller(data) }.notify(Call::Event(Event::Commit));
+    // Returning zero lets the commit proceed
+    0
+}
+
+unsafe extern "C" fn rollback_hook(data: *mut c_void) {
+    unsafe { caller(data) }.notify(Call::Event(Event::Rollback));
+}
+
+unsafe extern "C" fn destroy(data: *mut c_void) {
+    drop(unsafe { Box::from_raw(data as *mut Caller) });
+}
@@ -0,0 +1,159 @@
+use futures::StreamExt;
+use rquickjs::{
+    CaughtError, Class, Ctx, JsLifetime, Object, Persistent, Result, Value,
+    atom::PredefinedAtom,
+    class::Trace,
+    function::{Opt, This},
+};
+use sqlx::sqlite::{SqliteRow, SqliteStatement};
+use sqlx::{Executor, Statement as _};
+use tokio::sync::{Mutex, mpsc};
+
+use super::Argument;
+use super::connection::{Connection, Iterating};
+use super::error::ResultExt as _;
+use super::row::RowReader;
+
+enum Message {
+    Row(SqliteRow),
+    Error(sqlx::Error),
+    Exception(Persistent<Value<'static>>),
+}
+
+impl Message {
+    fn exception(ctx: &Ctx<'_>, err: rquickjs::Error) -> Self {
+        let err = CaughtError::from_error(ctx, err);
+        let _ = err.throw(ctx);
+        Message::Exception(Persistent::save(ctx, ctx.catch()))
+    }
+}
+
+/// Async iterator over the rows of a statement.
+///
+/// Rows are streamed from a task owning the connection, which is acquired on
+/// creation and released as soon as the iteration completes or the iterator
+/// is closed.
+#[derive(Trace, JsLifetime)]
+#[rquickjs::class]
+pub struct RowIterator {
+    #[qjs(skip_trace)]
+    receiver: Mutex<Option<mpsc::Receiver<Message>>>,
+    #[qjs(skip_trace)]
+    iterating: Option<Iterating>,
+    #[qjs(skip_trace)]
+    reader: RowReader,
+    #[qjs(skip_trace)]
+    sql: String,
+}
+
+impl RowIterator {
+    pub fn spawn<'js>(
+        ctx: &Ctx<'js>,
+        stmt: SqliteStatement<'static>,
+        arguments: Vec<Argument<'js>>,
+        connection: Connection,
+        reader: RowReader,
+    ) -> Self {
+        let sql = stmt.sql().to_owned();
+        let iterating = connection.iterating();
+        let (sender, receiver) = mpsc::channel(1);
+        let task_ctx = ctx.clone();
+        let task_iterating = iterating.clone();
+        ctx.spawn(async move {
+            let ctx = task_ctx;
+            let mut conn = match connection.acquire(&ctx).await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    let _ = sender.send(Message::exception(&ctx, err)).await;
+                    return;
+                }
+            };
+            let _holding = task_iterating.as_ref().map(Iterating::hold);
+            let mut query = stmt.query();
+            for argument in &arguments {
+                if let Err(err) = argument.try_bind(&ctx, &mut query) {
+                    let _ = sender.send(Message::exception(&ctx, err)).await;
+                    return;
+                }
+            }
+
+            let mut rows = (&mut *conn).fetch(query);
+            loop {
+                tokio::select! {
+                    row = rows.next() => {
+                        let Some(row) = row else {
+                            break;
+                        };
+                        let (message, failed) = match row {
+                            Ok(row) => (Message::Row(row), false),
+                            Err(err) => (Message::Error(err), true),
+                        };
+                        if sender.send(message).await.is_err() || failed {
+                            break;
+                        }
+                    }
+                    _ = sender.closed() => break,
+                }
+            }
+        });
+        Self {
+            receiver: Mutex::new(Some(receiver)),
+            iterating,
+            reader,
+            sql,
+        }
+    }
+
+    fn result<'js>(ctx: &Ctx<'js>, value: Value<'js>, done: bool) -> Result<Object<'js>> {
+        let result = Object::new(ctx.clone())?;
+        result.set(PredefinedAtom::Value, value)?;
+        result.set(PredefinedAtom::Done, done)?;
+        Ok(result)
+    }
+}
+
+#[rquickjs::methods(rename_all = "camelCase")]
+impl RowIterator {
+    async fn next<'js>(&self, ctx: Ctx<'js>) -> Result<Object<'js>> {
+        let mut receiver = self.receiver.lock().await;
+        let row = match receiver.as_mut() {
+            Some(receiver) => receiver.recv().await,
+            None => None,
+        };
+        match row {
+            Some(Message::Row(row)) => {
+                let value = self.reader.read(&ctx, &row)?;
+                Self::result(&ctx, value, false)
+            }
+            Some(Message::Error(err)) => {
+                *receiver = None;
+                Err(err).or_throw_sql(&ctx, &self.sql)
+            }
+            Some(Message::Exception(err)) => {
+                *receiver = None;
+                Err(ctx.throw(err.restore(&ctx)?))
+            }
+            None => {
+                *receiver = None;
+                Self::result(&ctx, Value::new_undefined(ctx.clone()), true)
+            }
+        }
+    }
+
+    #[qjs(rename = "return")]
+    async fn finish<'js>(&self, ctx: Ctx<'js>, value: Opt<Value<'js>>) -> Result<Object<'js>> {
+        // Dropping the receiver stops the task and releases the connection,
+        // queries can wait for it from now on
+        self.receiver.lock().await.take();
+        if let Some(iterating) = &self.iterating {
+            iterating.release();
+        }
+        let value = value.0.unwrap_or_else(|| Value::new_undefined(ctx.clone()));
+        Self::result(&ctx, value, true)
+    }
+
+    #[qjs(rename = PredefinedAtom::SymbolAsyncIterator)]
+    fn async_iterator<'js>(this: This<Class<'js, Self>>) -> Class<'js, Self> {
+        this.0
+    }
+}
@@ -7,21 +7,62 @@ use rquickjs_extra_utils::module::export_default;
 
 pub use self::argument::Argument;
 pub use self::database::Database;
+pub use self::database_sync::DatabaseSync;
+pub use self::error::SqliteError;
 pub use self::open::{OpenOptions, open};
 pub use self::statement::Statement;
+pub use self::statement_sync::StatementSync;
+pub use self::transaction::Transaction;
 pub use self::value::Value;
 
+mod aggregate;
 mod argument;
+mod backup;
+mod blob;
+mod callback;
+mod cancel;
+mod collation;
+mod connection;
 mod database;
+mod database_sync;
+mod error;
+#[cfg(feature = "sqlite-extensions")]
+mod extension;
+mod function;
+mod hooks;
+mod iterator;
+mod migrate;
 mod open;
+mod parameters;
+mod pragma;
+mod raw;
+mod registry;
+mod row;
+mod serialize;
+#[cfg(feature = "session")]
+mod session;
 mod statement;
+mod statement_sync;
+mod transaction;
 mod value;
 
+#[cfg(all(feature = "session", not(sqlite_session)))]
+compile_error!(
+    "the `session` feature needs the session extension of SQLite, build with \
+     LIBSQLITE3_FLAGS=\"-DSQLITE_ENABLE_SESSION -DSQLITE_ENABLE_PREUPDATE_HOOK\""
+);
+
+/// The target of the log records of the module.
+const TARGET: &str = "sqlite";
+
 pub struct SqliteModule;
 
 impl ModuleDef for SqliteModule {
     fn declare(declare: &Declarations) -> Result<()> {
         declare.declare(stringify!(Database))?;
+        declare.declare(stringify!(DatabaseSync))?;
+        declare.declare(stringify!(StatementSync))?;
+        declare.declare(stringify!(SqliteError))?;
         declare.declare("open")?;
         declare.declare("default")?;
 
@@ -31,6 +72,9 @@ impl ModuleDef for SqliteModule {
     fn evaluate<'js>(ctx: &Ctx<'js>, exports: &Exports<'js>) -> Result<()> {
         export_default(ctx, exports, |default| {
             Class::<Database>::define(default)?;
+            Class::<DatabaseSync>::define(default)?;
+            Class::<StatementSync>::define(default)?;
+            SqliteError::define(default)?;
 
             default.set("open", Func::from(Async(open::open)))?;
 
@@ -0,0 +1,316 @@
+use rquickjs::{
+    Class, Ctx, Exception, FromJs, Function, Object, Result, Value, promise::MaybePromise,
+};
+use sqlx::SqlitePool;
+
+use super::Transaction;
+use super::error::ResultExt as _;
+use super::value::ReadOptions;
+
+/// A step of a migration, either SQL or a function called with the transaction.
+enum Step<'js> {
+    Sql(String),
+    Function(Function<'js>),
+}
+
+impl<'js> Step<'js> {
+    fn from_js(ctx: &Ctx<'js>, id: i32, name: &str, value: Value<'js>) -> Result<Option<Self>> {
+        if value.is_undefined() || value.is_null() {
+            return Ok(None);
+        } else if let Some(string) = value.as_string() {
+            return Ok(Some(Step::Sql(string.to_string()?)));
+        } else if let Some(function) = value.into_function() {
+            return Ok(Some(Step::Function(function)));
+        }
+        Err(Exception::throw_type(
+            ctx,
+            &[
+                "Invalid '",
+                name,
+                "' of migration ",
+                &id.to_string(),
+                ": expected SQL or a function",
+            ]
+            .concat(),
+        ))
+    }
+
+    async fn run(&self, ctx: &Ctx<'js>, tx: &Transaction) -> Result<()> {
+        match self {
+            Step::Sql(sql) => {
+                let mut conn = tx.connection().acquire(ctx).await?;
+                sqlx::raw_sql(sql)
+                    .execute(&mut *conn)
+                    .await
+                    .or_throw_sql(ctx, sql)?;
+            }
+            Step::Function(function) => {
+                let instance = Class::instance(ctx.clone(), tx.clone())?;
+                function
+                    .call::<_, MaybePromise>((instance,))?
+                    .into_future::<Value>()
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A migration, identified by the `user_version` of the database once applied.
+pub struct Migration<'js> {
+    id: i32,
+    up: Step<'js>,
+    down: Option<Step<'js>>,
+}
+
+impl<'js> FromJs<'js> for Migration<'js> {
+    fn from_js(ctx: &Ctx<'js>, value: Value<'js>) -> Result<Self> {
+        let Some(obj) = value.into_object() else {
+            return Err(Exception::throw_type(ctx, "Migrations must be objects"));
+        };
+        let id = obj
+            .get::<_, Value<'js>>("id")?
+            .as_number()
+            .filter(|id| id.fract() == 0.0 && *id >= 1.0 && *id <= i32::MAX as f64)
+            .ok_or_else(|| {
+                Exception::throw_type(
+                    ctx,
+                    "Invalid migration id: expected a positive integer, it is stored in user_version",
+                )
+            })? as i32;
+        let up = Step::from_js(ctx, id, "up", obj.get("up")?)?.ok_or_else(|| {
+            Exception::throw_type(
+                ctx,
+                &["Migration ", &id.to_string(), " has no 'up'"].concat(),
+            )
+        })?;
+        let down = Step::from_js(ctx, id, "down", obj.get("down")?)?;
+        Ok(Self { id, up, down })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrateOptions {
+    /// The version to migrate to, the last migration by default.
+    pub to: Option<i32>,
+    /// The table recording the applied migrations.
+    pub table: String,
+}
+
+impl Default for MigrateOptions {
+    fn default() -> Self {
+        Self {
+            to: None,
+            table: "_migrations".into(),
+        }
+    }
+}
+
+impl<'js> FromJs<'js> for MigrateOptions {
+    fn from_js(ctx: &Ctx<'js>, value: Value<'js>) -> Result<Self> {
+        let default = MigrateOptions::default();
+        let obj = value.get::<Object<'js>>()?;
+        let to = match obj.get::<_, Value<'js>>("to")? {
+            to if to.is_undefined() => None,
+            to => Some(
+                to.as_number()
+                    .filter(|to| to.fract() == 0.0 && *to >= 0.0 && *to <= i32::MAX as f64)
+                    .ok_or_else(|| {
+                        Exception::throw_type(
+                            ctx,
+                            "Invalid option 'to': expected a migration id or 0",
+                        )
+                    })? as i32,
+            ),
+        };
+        let table = obj
+            .get::<_, Option<String>>("table")?
+            .unwrap_or(default.table);
+        Ok(Self { to, table })
+    }
+}
+
+/// Applies or reverts migrations in a single immediate transaction, so
+/// concurrent runs wait for each other and a failure leaves the database
+/// untouched.
+///
+/// The version of the database is its `user_version`, the ids of the applied
+/// migrations are also recorded in a table to detect migrations added
+/// before ones that were already applied.
+pub async fn migrate<'js>(
+    ctx: &Ctx<'js>,
+    pool: &SqlitePool,
+    migrations: Vec<Migration<'js>>,
+    options: MigrateOptions,
+    read_options: ReadOptions,
+) -> Result<Object<'js>> {
+    if let Some(pair) = migrations.windows(2).find(|pair| pair[0].id >= pair[1].id) {
+        return Err(Exception::throw_type(
+            ctx,
+            &[
+                "Migration ids must be increasing, ",
+                &pair[1].id.to_string(),
+                " follows ",
+                &pair[0].id.to_string(),
+            ]
+            .concat(),
+        ));
+    }
+    let target = match options.to {
+        Some(to) if to != 0 && !migrations.iter().any(|m| m.id == to) => {
+            return Err(Exception::throw_range(
+                ctx,
+                &["Unknown migration ", &to.to_string()].concat(),
+            ));
+        }
+        Some(to) => to,
+        None => migrations.last().map_or(0, |m| m.id),
+    };
+
+    let tx = Transaction::start_with(ctx, pool, read_options, "BEGIN IMMEDIATE").await?;
+    let result = run(ctx, &tx, &migrations, &options.table, target).await;
+    tx.finish_with(ctx, result).await
+}
+
+async fn run<'js>(
+    ctx: &Ctx<'js>,
+    tx: &Transaction,
+    migrations: &[Migration<'js>],
+    table: &str,
+    target: i32,
+) -> Result<Object<'js>> {
+    let table = ["\"", &table.replace('"', "\"\""), "\""].concat();
+    let (version, applied) = {
+        let mut conn = tx.connection().acquire(ctx).await?;
+        let create = [
+            "CREATE TABLE IF NOT EXISTS ",
+            &table,
+            " (id INTEGER PRIMARY KEY, applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP)",
+        ]
+        .concat();
+        sqlx::raw_sql(&create)
+            .execute(&mut *conn)
+            .await
+            .or_throw_sql(ctx, &create)?;
+        let version = sqlx::query_scalar::<_, i32>("PRAGMA user_version")
+            .fetch_one(&mut *conn)
+            .await
+            .or_throw_sqlite(ctx)?;
+        let select = ["SELECT id FROM ", &table, " ORDER BY id"].concat();
+        let applied = sqlx::query_scalar::<_, i32>(&select)
+            .fetch_all(&mut *conn)
+            .await
+            .or_throw_sql(ctx, &select)?;
+        (version, applied)
+    };
+    check_history(ctx, migrations, version, &applied)?;
+
+    let ids = rquickjs::Array::new(ctx.clone())?;
+    if target >= version {
+        let insert = ["INSERT INTO ", &table, " (id) VALUES (?)"].concat();
+        for migration in migrations
+            .iter()
+            .filter(|m| m.id > version && m.id <= target)
+        {
+            migration.up.run(ctx, tx).await?;
+            let mut conn = tx.connection().acquire(ctx).await?;
+            sqlx::query(&insert)
+                .bind(migration.id)
+                .execute(&mut *conn)
+                .await
+                .or_throw_sql(ctx, &insert)?;
+            ids.set(ids.len(), migration.id)?;
+        }
+    } else {
+        let reverted: Vec<_> = migrations
+            .iter()
+            .rev()
+            .filter(|m| m.id > target && m.id <= version)
+            .collect();
+        if let Some(migration) = reverted.iter().find(|m| m.down.is_none()) {
+            return Err(Exception::throw_message(
+                ctx,
+                &[
+                    "Migration ",
+                    &migration.id.to_string(),
+                    " has no 'down' and can not be reverted",
+                ]
+                .concat(),
+            ));
+        }
+        let delete = ["DELETE FROM ", &table, " WHERE id = ?"].concat();
+        for migration in reverted {
+            if let Some(down) = &migration.down {
+                down.run(ctx, tx).await?;
+            }
+            let mut conn = tx.connection().acquire(ctx).await?;
+            sqlx::query(&delete)
+                .bind(migration.id)
+                .execute(&mut *conn)
+                .await
+                .or_throw_sql(ctx, &delete)?;
+            ids.set(ids.len(), migration.id)?;
+        }
+    }
+
+    // The version is an integer, it can not be bound in a pragma
+    let pragma = ["PRAGMA user_version = ", &target.to_string()].concat();
+    let mut conn = tx.connection().acquire(ctx).await?;
+    sqlx::raw_sql(&pragma)
+        .execute(&mut *conn)
+        .await
+        .or_throw_sql(ctx, &pragma)?;
+
+    let result = Object::new(ctx.clone())?;
+    result.set("from", version)?;
+    result.set("to", target)?;
+    result.set("migrations", ids)?;
+    Ok(result)
+}
+
+/// Refuses to migrate a database whose history does not match `migrations`.
+fn check_history(
+    ctx: &Ctx<'_>,
+    migrations: &[Migration<'_>],
+    version: i32,
+    applied: &[i32],
+) -> Result<()> {
+    let last = applied.last().copied().unwrap_or(0);
+    if last != version {
+        return Err(Exception::throw_message(
+            ctx,
+            &[
+                "Database version ",
+                &version.to_string(),
+                " does not match the last applied migration ",
+                &last.to_string(),
+            ]
+            .concat(),
+        ));
+    }
+    if let Some(id) = applied
+        .iter()
+        .find(|id| !migrations.iter().any(|m| m.id == **id))
+    {
+        return Err(Exception::throw_message(
+            ctx,
+            &["Applied migration ", &id.to_string(), " is unknown"].concat(),
+        ));
+    }
+    if let Some(migration) = migrations
+        .iter()
+        .find(|m| m.id < version && !applied.contains(&m.id))
+    {
+        return Err(Exception::throw_message(
+            ctx,
+            &[
+                "Migration ",
+                &migration.id.to_string(),
+                " was added before the applied migration ",
+                &version.to_string(),
+            ]
+            .concat(),
+        ));
+    }
+    Ok(())
+}
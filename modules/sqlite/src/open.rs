@@ -1,37 +1,66 @@
 use std::{
     path::PathBuf,
+    str::FromStr,
     sync::atomic::{AtomicUsize, Ordering},
     time::Duration,
 };
 
-use either::Either;
-use rquickjs::{Ctx, FromJs, Null, Object, Result, Value};
-use rquickjs_extra_utils::result::ResultExt;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use libsqlite3_sys::SQLITE_ERROR;
+use rquickjs::{Ctx, Exception, FromJs, Object, Result, TypedArray, Value};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
 
 use super::Database;
+use super::error::SqliteError;
+use super::registry::Registry;
+use super::serialize;
+use super::value::{MAX_SAFE_INTEGER, ReadOptions};
 
 static IN_MEMORY_DB_SEQ: AtomicUsize = AtomicUsize::new(0);
 
 pub async fn open(ctx: Ctx<'_>, options: OpenOptions) -> Result<Database> {
-    let mut connect_options = SqliteConnectOptions::new();
+    let mut read_only = options.read_only || options.immutable;
+    let mut connect_options = match &options.uri {
+        Some(uri) => {
+            read_only |= is_read_only_uri(uri);
+            parse_uri(uri).map_err(|err| err.context("Invalid database URI").throw(&ctx))?
+        }
+        None => SqliteConnectOptions::new(),
+    };
     connect_options = connect_options
         .foreign_keys(options.foreign_keys)
         .page_size(options.page_size)
         .busy_timeout(options.busy_timeout)
         .thread_name(|id| format!("quickjs-sqlite-worker-{id}"));
-    if let Some(filename) = options.filename {
-        connect_options = connect_options.filename(filename).create_if_missing(true);
+    // The location of a URI database is only given by the URI
+    if options.uri.is_none() {
+        if let Some(filename) = options.filename {
+            connect_options = connect_options
+                .filename(filename)
+                .create_if_missing(options.create);
+        }
+        if options.in_memory {
+            let seqno = IN_MEMORY_DB_SEQ.fetch_add(1, Ordering::Relaxed);
+            connect_options = connect_options
+                .filename(format!("file:sqlite-in-memory-{seqno}"))
+                .in_memory(true)
+                .shared_cache(true);
+        }
     }
-    if options.in_memory {
-        let seqno = IN_MEMORY_DB_SEQ.fetch_add(1, Ordering::Relaxed);
-        connect_options = connect_options
-            .filename(format!("file:sqlite-in-memory-{seqno}"))
-            .in_memory(true)
-            .shared_cache(true);
+    if options.read_only || options.immutable {
+        connect_options = connect_options.read_only(true);
     }
-    if options.wal {
-        connect_options = connect_options.journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+    if options.immutable {
+        connect_options = connect_options.immutable(true);
+    }
+    // The journal mode of a read-only database cannot be changed
+    let journal_mode = options
+        .journal_mode
+        .or_else(|| (options.wal && !read_only).then_some(SqliteJournalMode::Wal));
+    if let Some(journal_mode) = journal_mode {
+        connect_options = connect_options.journal_mode(journal_mode);
+    }
+    if let Some(synchronous) = options.synchronous {
+        connect_options = connect_options.synchronous(synchronous);
     }
 
     let mut pool_options = SqlitePoolOptions::new();
@@ -41,18 +70,110 @@ pub async fn open(ctx: Ctx<'_>, options: OpenOptions) -> Result<Database> {
         .max_connections(options.max_connections)
         .min_connections(options.min_connections);
 
+    let registry = Registry::default();
+    let after_connect = registry.clone();
+    let before_acquire = registry.clone();
+    pool_options = pool_options
+        .after_connect(move |conn, _| {
+            let registry = after_connect.clone();
+            Box::pin(async move { registry.install(conn).await })
+        })
+        .before_acquire(move |conn, _| {
+            let registry = before_acquire.clone();
+            Box::pin(async move { registry.install(conn).await.map(|_| true) })
+        });
+
     let pool = pool_options
         .connect_with(connect_options)
         .await
-        .or_throw_msg(&ctx, "Unable to open database")?;
-    Ok(Database::new(pool))
+        .map_err(|err| match SqliteError::from_sqlx(&err) {
+            Some(error) => error.context("Unable to open database").throw(&ctx),
+            None => Exception::throw_message(
+                &ctx,
+                &["Unable to open database: ", &err.to_string()].concat(),
+            ),
+        })?;
+    if let Some(data) = &options.data {
+        serialize::deserialize(&ctx, &pool, data).await?;
+    }
+    let read_options = ReadOptions {
+        declared_types: options.declared_types,
+        safe_integers: options.safe_integers,
+    };
+    Ok(Database::with_options(
+        pool,
+        registry,
+        read_options,
+        options.allow_extension,
+    ))
+}
+
+/// The query parameters of a URI understood by sqlx, other parameters are
+/// rejected when the options are read rather than ignored.
+const URI_PARAMETERS: &[&str] = &["mode", "cache", "immutable", "vfs"];
+
+/// Parses a `file:` URI, see <https://www.sqlite.org/uri.html>.
+fn parse_uri(uri: &str) -> std::result::Result<SqliteConnectOptions, SqliteError> {
+    let Some(path) = uri.strip_prefix("file:") else {
+        return Err(SqliteError::new(
+            SQLITE_ERROR,
+            "URI must start with 'file:'",
+        ));
+    };
+    // Only an empty or local authority is allowed before an absolute path
+    let path = match path.strip_prefix("//") {
+        Some(rest) => match rest.find('/') {
+            Some(0) => rest,
+            Some(9) if rest.starts_with("localhost") => &rest[9..],
+            _ => {
+                return Err(SqliteError::new(
+                    SQLITE_ERROR,
+                    "URI authority must be empty or 'localhost'",
+                ));
+            }
+        },
+        None => path,
+    };
+    SqliteConnectOptions::from_str(path).map_err(|err| {
+        let message = match err {
+            sqlx::Error::Configuration(err) => err.to_string(),
+            err => err.to_string(),
+        };
+        SqliteError::new(SQLITE_ERROR, message)
+    })
+}
+
+/// The first query parameter of a URI that is not supported.
+fn unsupported_uri_parameter(uri: &str) -> Option<&str> {
+    let (_, query) = uri.split_once('?')?;
+    query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| param.split_once('=').map_or(param, |(name, _)| name))
+        .find(|name| !URI_PARAMETERS.contains(name))
+}
+
+/// Whether the query parameters of a URI open the database read-only.
+fn is_read_only_uri(uri: &str) -> bool {
+    let Some((_, query)) = uri.split_once('?') else {
+        return false;
+    };
+    query
+        .split('&')
+        .any(|param| matches!(param, "mode=ro" | "immutable=1" | "immutable=true"))
 }
 
 #[derive(Debug, Clone)]
 pub struct OpenOptions {
     pub filename: Option<PathBuf>,
+    pub uri: Option<String>,
     pub in_memory: bool,
+    pub read_only: bool,
+    pub create: bool,
+    pub immutable: bool,
     pub wal: bool,
+    pub journal_mode: Option<SqliteJournalMode>,
+    pub synchronous: Option<SqliteSynchronous>,
     pub page_size: u32,
     pub foreign_keys: bool,
     pub max_connections: u32,
@@ -60,14 +181,24 @@ pub struct OpenOptions {
     pub idle_timeout: Option<Duration>,
     pub max_lifetime: Option<Duration>,
     pub busy_timeout: Duration,
+    pub declared_types: bool,
+    pub safe_integers: bool,
+    pub allow_extension: bool,
+    pub data: Option<Vec<u8>>,
 }
 
 impl Default for OpenOptions {
     fn default() -> Self {
         Self {
             filename: None,
+            uri: None,
             in_memory: true,
+            read_only: false,
+            create: true,
+            immutable: false,
             wal: true,
+            journal_mode: None,
+            synchronous: None,
             page_size: 4096,
             foreign_keys: true,
             max_connections: 5,
@@ -75,51 +206,486 @@ impl Default for OpenOptions {
             idle_timeout: None,
             max_lifetime: Some(Duration::from_secs(60 * 60)),
             busy_timeout: Duration::from_millis(5 * 1000),
+            declared_types: false,
+            safe_integers: false,
+            allow_extension: false,
+            data: None,
         }
     }
 }
 
+const OPTIONS: &[&str] = &[
+    "filename",
+    "uri",
+    "inMemory",
+    "readOnly",
+    "create",
+    "immutable",
+    "wal",
+    "journalMode",
+    "synchronous",
+    "pageSize",
+    "foreignKeys",
+    "enableForeignKeyConstraints",
+    "maxConnections",
+    "minConnections",
+    "idleTimeout",
+    "idleTimeoutMs",
+    "maxLifetime",
+    "maxLifetimeMs",
+    "busyTimeout",
+    "timeout",
+    "declaredTypes",
+    "safeIntegers",
+    "allowExtension",
+    "data",
+];
+
+/// Reads the fields of an options object, throwing a `TypeError` naming the
+/// field when its value has the wrong type.
+struct Fields<'a, 'js> {
+    ctx: &'a Ctx<'js>,
+    obj: Object<'js>,
+}
+
+impl<'js> Fields<'_, 'js> {
+    fn value(&self, name: &str) -> Result<Option<Value<'js>>> {
+        let value = self.obj.get::<_, Value<'js>>(name)?;
+        Ok((!value.is_undefined()).then_some(value))
+    }
+
+    fn invalid(&self, name: &str, expected: &str) -> rquickjs::Error {
+        Exception::throw_type(
+            self.ctx,
+            &["Invalid option '", name, "': expected ", expected].concat(),
+        )
+    }
+
+    fn bool(&self, name: &str) -> Result<Option<bool>> {
+        match self.value(name)? {
+            Some(value) => match value.as_bool() {
+                Some(value) => Ok(Some(value)),
+                None => Err(self.invalid(name, "a boolean")),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn string(&self, name: &str) -> Result<Option<String>> {
+        match self.value(name)? {
+            Some(value) if value.is_string() => Ok(Some(value.get()?)),
+            Some(_) => Err(self.invalid(name, "a string")),
+            None => Ok(None),
+        }
+    }
+
+    fn integer(&self, name: &str, max: u64) -> Result<Option<u64>> {
+        match self.value(name)? {
+            Some(value) => match value.as_number() {
+                Some(number) if number.fract() == 0.0 && (0.0..=max as f64).contains(&number) => {
+                    Ok(Some(number as u64))
+                }
+                _ => Err(self.invalid(
+                    name,
+                    &["an integer between 0 and ", &max.to_string()].concat(),
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Reads a duration in multiples of `unit` milliseconds, up to `max` milliseconds.
+    fn duration(&self, name: &str, unit: u64, max: u64) -> Result<Option<Duration>> {
+        Ok(self
+            .integer(name, max / unit)?
+            .map(|value| Duration::from_millis(value * unit)))
+    }
+
+    /// Reads a duration that is disabled by `null`, returned as `Some(None)`.
+    fn optional_duration(&self, name: &str, unit: u64) -> Result<Option<Option<Duration>>> {
+        if self.value(name)?.is_some_and(|value| value.is_null()) {
+            return Ok(Some(None));
+        }
+        Ok(self
+            .duration(name, unit, MAX_SAFE_INTEGER as u64)?
+            .map(Some))
+    }
+
+    /// Reads one of two names of the same option, which cannot both be set.
+    fn either<T>(
+        &self,
+        name: &str,
+        alias: &str,
+        read: impl Fn(&Self, &str) -> Result<Option<T>>,
+    ) -> Result<Option<T>> {
+        match (read(self, name)?, read(self, alias)?) {
+            (Some(_), Some(_)) => Err(conflict(self.ctx, name, alias)),
+            (value, alias) => Ok(value.or(alias)),
+        }
+    }
+
+    fn parse<T: FromStr>(&self, name: &str, expected: &str) -> Result<Option<T>> {
+        match self.string(name)? {
+            Some(value) => T::from_str(&value)
+                .map(Some)
+                .map_err(|_| self.invalid(name, expected)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn conflict(ctx: &Ctx<'_>, name: &str, other: &str) -> rquickjs::Error {
+    Exception::throw_type(
+        ctx,
+        &[
+            "Options '",
+            name,
+            "' and '",
+            other,
+            "' cannot be used together",
+        ]
+        .concat(),
+    )
+}
+
 impl<'js> FromJs<'js> for OpenOptions {
-    fn from_js(_ctx: &Ctx<'js>, value: Value<'js>) -> Result<Self> {
+    fn from_js(ctx: &Ctx<'js>, value: Value<'js>) -> Result<Self> {
         let default = OpenOptions::default();
-        let obj = value.get::<Object<'js>>()?;
-        let filename = obj.get::<_, String>("filename").map(PathBuf::from).ok();
-        let in_memory = obj.get::<_, bool>("inMemory").unwrap_or(default.in_memory);
-        let wal = obj.get::<_, bool>("wal").unwrap_or(default.wal);
-        let page_size = obj.get::<_, u32>("pageSize").unwrap_or(default.page_size);
-        let foreign_keys = obj
-            .get::<_, bool>("foreignKeys")
-            .unwrap_or(default.foreign_keys);
-        let max_connections = obj
-            .get::<_, u32>("maxConnections")
-            .unwrap_or(default.max_connections);
-        let min_connections = obj
-            .get::<_, u32>("minConnections")
-            .unwrap_or(default.min_connections);
-        let idle_timeout = obj
-            .get::<_, Option<u64>>("idleTimeout")?
-            .map(Duration::from_secs);
-        let max_lifetime =
-            obj.get::<_, Either<Option<u64>, Null>>("maxLifetime")
-                .map(|e| match e {
-                    Either::Left(s) => s.map(Duration::from_secs).or(default.max_lifetime),
-                    Either::Right(_) => None,
-                })?;
-        let busy_timeout = obj
-            .get::<_, u64>("busyTimeout")
-            .map(Duration::from_secs)
-            .unwrap_or(default.busy_timeout);
+        let obj = value
+            .into_object()
+            .ok_or_else(|| Exception::throw_type(ctx, "Options must be an object"))?;
+        for key in obj.keys::<String>() {
+            let key = key?;
+            if !OPTIONS.contains(&key.as_str()) {
+                return Err(Exception::throw_type(
+                    ctx,
+                    &["Unknown option '", &key, "'"].concat(),
+                ));
+            }
+        }
+        let fields = Fields { ctx, obj };
+
+        let filename = fields.string("filename")?.map(PathBuf::from);
+        let uri = fields.string("uri")?;
+        if let Some(name) = uri.as_deref().and_then(unsupported_uri_parameter) {
+            return Err(Exception::throw_type(
+                ctx,
+                &[
+                    "Unsupported URI parameter '",
+                    name,
+                    "', expected one of mode, cache, immutable or vfs",
+                ]
+                .concat(),
+            ));
+        }
+        let in_memory = fields.bool("inMemory")?;
+        let read_only = fields.bool("readOnly")?;
+        let create = fields.bool("create")?;
+        let immutable = fields.bool("immutable")?;
+        let wal = fields.bool("wal")?;
+        let journal_mode = fields.parse::<SqliteJournalMode>(
+            "journalMode",
+            "one of delete, truncate, persist, memory, wal or off",
+        )?;
+        let synchronous = fields
+            .parse::<SqliteSynchronous>("synchronous", "one of off, normal, full or extra")?;
+        let page_size = match fields.integer("pageSize", 65536)? {
+            Some(size) if (512..=65536).contains(&size) && size.is_power_of_two() => size as u32,
+            Some(_) => {
+                return Err(fields.invalid("pageSize", "a power of two between 512 and 65536"));
+            }
+            None => default.page_size,
+        };
+        let foreign_keys =
+            fields.either("foreignKeys", "enableForeignKeyConstraints", Fields::bool)?;
+        let max_connections = match fields.integer("maxConnections", u32::MAX as u64)? {
+            Some(0) => return Err(fields.invalid("maxConnections", "a positive integer")),
+            Some(max) => max as u32,
+            None => default.max_connections,
+        };
+        let min_connections = fields
+            .integer("minConnections", u32::MAX as u64)?
+            .map_or(default.min_connections, |min| min as u32);
+        // Pool durations are in seconds, unless their name ends with `Ms`
+        let idle_timeout = fields.either("idleTimeout", "idleTimeoutMs", |fields, name| {
+            fields.optional_duration(name, if name.ends_with("Ms") { 1 } else { 1000 })
+        })?;
+        let max_lifetime = fields.either("maxLifetime", "maxLifetimeMs", |fields, name| {
+            fields.optional_duration(name, if name.ends_with("Ms") { 1 } else { 1000 })
+        })?;
+        // SQLite takes the busy timeout as an `int` of milliseconds
+        let busy_timeout = fields.either("busyTimeout", "timeout", |fields, name| {
+            fields.duration(name, 1, i32::MAX as u64)
+        })?;
+        let declared_types = fields.bool("declaredTypes")?;
+        let safe_integers = fields.bool("safeIntegers")?;
+        let allow_extension = fields.bool("allowExtension")?;
+        if allow_extension == Some(true) && !cfg!(feature = "sqlite-extensions") {
+            return Err(fields.invalid(
+                "allowExtension",
+                "false, rquickjs-extra-sqlite must be built with the sqlite-extensions feature to load extensions",
+            ));
+        }
+        let data = match fields.value("data")? {
+            Some(data) => {
+                let data = TypedArray::<u8>::from_value(data)
+                    .map_err(|_| fields.invalid("data", "a Uint8Array"))?;
+                let bytes = data
+                    .as_bytes()
+                    .ok_or_else(|| fields.invalid("data", "a Uint8Array that is not detached"))?;
+                Some(bytes.to_vec())
+            }
+            None => None,
+        };
+
+        if filename.is_some() && in_memory == Some(true) {
+            return Err(conflict(ctx, "inMemory", "filename"));
+        }
+        if uri.is_some() {
+            if filename.is_some() {
+                return Err(conflict(ctx, "uri", "filename"));
+            }
+            if in_memory.is_some() {
+                return Err(conflict(ctx, "uri", "inMemory"));
+            }
+        }
+        let in_memory = in_memory.unwrap_or(filename.is_none() && uri.is_none());
+        if data.is_some() {
+            if !in_memory {
+                return Err(Exception::throw_type(
+                    ctx,
+                    "The data option can only be used with in-memory databases",
+                ));
+            }
+            if read_only == Some(true) || immutable == Some(true) {
+                return Err(conflict(ctx, "data", "readOnly"));
+            }
+        }
+        if create == Some(true) && (read_only == Some(true) || immutable == Some(true)) {
+            return Err(conflict(ctx, "create", "readOnly"));
+        }
+        if let (Some(wal), Some(journal_mode)) = (wal, journal_mode)
+            && wal != matches!(journal_mode, SqliteJournalMode::Wal)
+        {
+            return Err(conflict(ctx, "wal", "journalMode"));
+        }
+        if min_connections > max_connections {
+            return Err(Exception::throw_type(
+                ctx,
+                "Invalid option 'minConnections': expected at most maxConnections",
+            ));
+        }
+
         Ok(Self {
             filename,
+            uri,
             in_memory,
-            wal,
+            read_only: read_only.unwrap_or(default.read_only),
+            create: create.unwrap_or(default.create),
+            immutable: immutable.unwrap_or(default.immutable),
+            wal: wal.unwrap_or(default.wal),
+            journal_mode,
+            synchronous,
             page_size,
-            foreign_keys,
+            foreign_keys: foreign_keys.unwrap_or(default.foreign_keys),
             max_connections,
             min_connections,
-            idle_timeout,
-            max_lifetime,
-            busy_timeout,
+            idle_timeout: idle_timeout.unwrap_or(default.idle_timeout),
+            max_lifetime: max_lifetime.unwrap_or(default.max_lifetime),
+            busy_timeout: busy_timeout.unwrap_or(default.busy_timeout),
+            declared_types: declared_types.unwrap_or(default.declared_types),
+            safe_integers: safe_integers.unwrap_or(default.safe_integers),
+            allow_extension: allow_extension.unwrap_or(default.allow_extension),
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rquickjs::CatchResultExt;
+    use rquickjs_extra_test::{ModuleEvaluator, call_test, test_async_with};
+
+    use super::{parse_uri, unsupported_uri_parameter};
+    use crate::SqliteModule;
+
+    #[test]
+    fn test_parse_uri() {
+        let options = parse_uri("file:data.db?mode=ro&immutable=1").unwrap();
+        assert_eq!(options.get_filename().to_str(), Some("data.db"));
+        let options = parse_uri("file:///tmp/data.db").unwrap();
+        assert_eq!(options.get_filename().to_str(), Some("/tmp/data.db"));
+        let options = parse_uri("file://localhost/tmp/data.db").unwrap();
+        assert_eq!(options.get_filename().to_str(), Some("/tmp/data.db"));
+        assert!(parse_uri("data.db").is_err());
+        assert!(parse_uri("file://example.com/data.db").is_err());
+        assert!(parse_uri("file:data.db?unknown=1").is_err());
+
+        let options = parse_uri("file:data.db?cache=private&vfs=unix-none").unwrap();
+        assert_eq!(options.get_filename().to_str(), Some("data.db"));
+        assert_eq!(
+            unsupported_uri_parameter("file:data.db?mode=ro&cache=shared"),
+            None
+        );
+        assert_eq!(
+            unsupported_uri_parameter("file:data.db?mode=ro&nolock=1"),
+            Some("nolock")
+        );
+        assert_eq!(unsupported_uri_parameter("file:data.db?psow"), Some("psow"));
+    }
+
+    #[tokio::test]
+    async fn test_open_modes() {
+        let path =
+            std::env::temp_dir().join(format!("rquickjs-sqlite-open-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let missing = path.with_extension("missing.db");
+        let filename = path.to_string_lossy().into_owned();
+        let missing_filename = missing.to_string_lossy().into_owned();
+
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        const fails = async (f) => {
+                            try {
+                                await f();
+                                return "ok";
+                            } catch (e) {
+                                return e.code ?? e.name;
+                            }
+                        };
+
+                        export async function test(filename, missing) {
+                            const db = await open({ filename, inMemory: false, journalMode: "delete", synchronous: "full" });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY)");
+                            await db.exec("INSERT INTO test (id) VALUES (1)");
+                            const mode = await db.prepare("PRAGMA journal_mode").then((s) => s.pluck().get());
+                            const sync = await db.prepare("PRAGMA synchronous").then((s) => s.pluck().get());
+                            await db.close();
+
+                            const results = [mode, sync];
+                            for (const options of [
+                                { filename, inMemory: false, readOnly: true },
+                                { filename, inMemory: false, immutable: true },
+                                { uri: `file:${filename}?mode=ro` },
+                            ]) {
+                                const db = await open(options);
+                                const count = await db.prepare("SELECT count(*) FROM test").then((s) => s.pluck().get());
+                                results.push(count, await fails(() => db.exec("INSERT INTO test (id) VALUES (2)")));
+                                results.push(await fails(() => db.backup(`${filename}.backup`)));
+                                await db.close();
+                            }
+                            results.push(await fails(() => open({ filename: missing, inMemory: false, create: false })));
+                            results.push(await fails(() => open({ filename: missing, inMemory: false, readOnly: true })));
+                            results.push(await fails(() => open({ journalMode: "fast" })));
+                            return results.join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result =
+                    call_test::<String, _>(&ctx, &module, (filename, missing_filename)).await;
+                assert_eq!(
+                    result,
+                    "delete,2,1,SQLITE_READONLY,SQLITE_READONLY,1,SQLITE_READONLY,SQLITE_READONLY,1,SQLITE_READONLY,SQLITE_READONLY,SQLITE_CANTOPEN,SQLITE_CANTOPEN,TypeError"
+                );
+            })
+        })
+        .await;
+
+        assert!(!missing.exists());
+        assert!(!path.with_extension("db.backup").exists());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_open_validation() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const errors = [];
+                            for (const options of [
+                                { busyTimout: 1000 },
+                                { busyTimeout: "1000" },
+                                { busyTimeout: -1 },
+                                { busyTimeout: 2 ** 31 },
+                                { timeout: Number.MAX_SAFE_INTEGER },
+                                { inMemory: "yes" },
+                                { pageSize: 1000 },
+                                { filename: "test.db", inMemory: true },
+                                { uri: "file:test.db", filename: "test.db" },
+                                { busyTimeout: 1000, timeout: 1000 },
+                                { wal: true, journalMode: "delete" },
+                                { minConnections: 3, maxConnections: 2 },
+                                { data: [1, 2, 3] },
+                                { uri: "file:test.db?mode=ro&nolock=1" },
+                            ]) {
+                                try {
+                                    await open(options);
+                                    errors.push("ok");
+                                } catch (e) {
+                                    errors.push(`${e.name}: ${e.message}`);
+                                }
+                            }
+
+                            const db = await open({ timeout: 250, enableForeignKeyConstraints: false, idleTimeoutMs: 500, maxLifetime: null });
+                            const timeout = await db.prepare("PRAGMA busy_timeout").then((s) => s.pluck().get());
+                            const foreignKeys = await db.prepare("PRAGMA foreign_keys").then((s) => s.pluck().get());
+                            await db.close();
+                            return [...errors, timeout, foreignKeys].join("\n");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result.lines().collect::<Vec<_>>(),
+                    [
+                        "TypeError: Unknown option 'busyTimout'",
+                        "TypeError: Invalid option 'busyTimeout': expected an integer between 0 and 2147483647",
+                        "TypeError: Invalid option 'busyTimeout': expected an integer between 0 and 2147483647",
+                        "TypeError: Invalid option 'busyTimeout': expected an integer between 0 and 2147483647",
+                        "TypeError: Invalid option 'timeout': expected an integer between 0 and 2147483647",
+                        "TypeError: Invalid option 'inMemory': expected a boolean",
+                        "TypeError: Invalid option 'pageSize': expected a power of two between 512 and 65536",
+                        "TypeError: Options 'inMemory' and 'filename' cannot be used together",
+                        "TypeError: Options 'uri' and 'filename' cannot be used together",
+                        "TypeError: Options 'busyTimeout' and 'timeout' cannot be used together",
+                        "TypeError: Options 'wal' and 'journalMode' cannot be used together",
+                        "TypeError: Invalid option 'minConnections': expected at most maxConnections",
+                        "TypeError: Invalid option 'data': expected a Uint8Array",
+                        "TypeError: Unsupported URI parameter 'nolock', expected one of mode, cache, immutable or vfs",
+                        "250",
+                        "0",
+                    ]
+                );
+            })
         })
+        .await;
     }
 }
@@ -0,0 +1,300 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+use rquickjs::{Ctx, Exception, FromJs, Object, Result, Value};
+
+use super::Argument;
+
+/// Parameters of a prepared statement, as reported by SQLite.
+#[derive(Debug, Clone, Default)]
+pub struct Parameters {
+    // Name of each parameter, `None` for anonymous ones
+    names: Vec<Option<String>>,
+}
+
+impl Parameters {
+    /// Takes the name of each parameter of a prepared statement.
+    pub fn new(names: Vec<Option<String>>) -> Self {
+        Self { names }
+    }
+
+    /// The SQL to prepare with sqlx.
+    ///
+    /// sqlx only binds anonymous (`?`) and numbered (`?NNN`) parameters, so
+    /// named parameters are replaced by the `?NNN` of their index. Tokens that
+    /// SQLite did not report as parameters are left as they are.
+    pub fn sql<'a>(&self, sql: &'a str) -> Cow<'a, str> {
+        if !self.is_named() {
+            return Cow::Borrowed(sql);
+        }
+        let bytes = sql.as_bytes();
+        let mut rewritten = String::with_capacity(sql.len());
+        let mut last = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let start = i;
+            match bytes[i] {
+                quote @ (b'\'' | b'"' | b'`') => {
+                    i += 1;
+                    while i < bytes.len() {
+                        if bytes[i] == quote {
+                            // Doubled quotes are escapes
+                            if bytes.get(i + 1) == Some(&quote) {
+                                i += 2;
+                                continue;
+                            }
+                            break;
+                        }
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                b'[' => {
+                    while i < bytes.len() && bytes[i] != b']' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                    while i < bytes.len() && bytes[i] != b'\n' {
+                        i += 1;
+                    }
+                }
+                b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                    i += 2;
+                    while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/'))
+                    {
+                        i += 1;
+                    }
+                    i += 2;
+                }
+                b':' | b'@' | b'$' => {
+                    i += 1;
+                    while i < bytes.len() && is_id_char(bytes[i]) {
+                        i += 1;
+                    }
+                    let name = &sql[start..i];
+                    if let Some(index) = self.names.iter().position(|n| n.as_deref() == Some(name))
+                    {
+                        rewritten.push_str(&sql[last..start]);
+                        rewritten.push('?');
+                        rewritten.push_str(&(index + 1).to_string());
+                        last = i;
+                    }
+                }
+                b if is_id_char(b) => {
+                    // Identifiers and numbers, which can contain `$`
+                    while i < bytes.len() && (is_id_char(bytes[i]) || bytes[i] == b'$') {
+                        i += 1;
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+        rewritten.push_str(&sql[last..]);
+        Cow::Owned(rewritten)
+    }
+
+    /// Number of parameters of the statement.
+    pub fn count(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Whether `key` names a parameter, with or without its prefix.
+    pub fn is_parameter(&self, key: &str) -> bool {
+        self.names
+            .iter()
+            .flatten()
+            .any(|name| matches_key(name, key))
+    }
+
+    /// Whether `values` bind every parameter, either by position or with
+    /// a trailing object of named parameters.
+    pub fn accepts<'js>(&self, ctx: &Ctx<'js>, values: &[Value<'js>]) -> Result<bool> {
+        if values.len() == self.names.len() {
+            return Ok(true);
+        }
+        let anonymous = self.names.iter().filter(|name| name.is_none()).count();
+        match values.last() {
+            Some(last) if self.is_named() && values.len() == anonymous + 1 => {
+                is_plain_object(ctx, last)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Whether the statement has `:name`, `@name` or `$name` parameters,
+    /// numbered ones (`?NNN`) are bound by position.
+    fn is_named(&self) -> bool {
+        self.names
+            .iter()
+            .flatten()
+            .any(|name| !name.starts_with('?'))
+    }
+
+    /// Converts the JS arguments to the values to bind, in parameter order.
+    ///
+    /// When the statement has named parameters, a trailing plain object binds
+    /// them by name, with or without their prefix. Other arguments bind
+    /// anonymous parameters in order.
+    pub fn arguments<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        values: Vec<Value<'js>>,
+    ) -> Result<Vec<Argument<'js>>> {
+        self.arguments_with(ctx, values, true)
+    }
+
+    /// Like [`Parameters::arguments`], names of named parameters must include
+    /// their prefix unless `allow_bare_names` is set.
+    pub fn arguments_with<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        mut values: Vec<Value<'js>>,
+        allow_bare_names: bool,
+    ) -> Result<Vec<Argument<'js>>> {
+        let named = match values.last() {
+            Some(value) if self.is_named() && is_plain_object(ctx, value)? => {
+                values.pop().and_then(|value| value.into_object())
+            }
+            _ => None,
+        };
+        let Some(named) = named else {
+            check_count(ctx, values.len(), self.names.len())?;
+            return values
+                .into_iter()
+                .map(|value| Argument::from_js(ctx, value))
+                .collect();
+        };
+
+        let mut arguments: Vec<Option<Argument<'js>>> = Vec::with_capacity(self.names.len());
+        arguments.resize_with(self.names.len(), || None);
+
+        for prop in named.props::<String, Value<'js>>() {
+            let (key, value) = prop?;
+            let mut matches = self
+                .names
+                .iter()
+                .enumerate()
+                .filter_map(|(index, name)| Some((index, name.as_deref()?)))
+                .filter(|(_, name)| {
+                    *name == key.as_str() || (allow_bare_names && matches_key(name, &key))
+                });
+            let Some((index, _)) = matches.next() else {
+                return Err(Exception::throw_type(
+                    ctx,
+                    &["Unknown named parameter '", &key, "'"].concat(),
+                ));
+            };
+            if let Some((_, other)) = matches.next() {
+                return Err(Exception::throw_type(
+                    ctx,
+                    &[
+                        "Ambiguous named parameter '",
+                        &key,
+                        "', it matches both '",
+                        self.names[index].as_deref().unwrap_or_default(),
+                        "' and '",
+                        other,
+                        "'",
+                    ]
+                    .concat(),
+                ));
+            }
+            arguments[index] = Some(Argument::from_js(ctx, value)?);
+        }
+
+        let anonymous = self.names.iter().filter(|name| name.is_none()).count();
+        check_count(ctx, values.len(), anonymous)?;
+        let mut positional = values.into_iter();
+        self.names
+            .iter()
+            .zip(arguments)
+            .map(|(name, argument)| match (name, argument) {
+                (_, Some(argument)) => Ok(argument),
+                (Some(name), None) => Err(Exception::throw_type(
+                    ctx,
+                    &["Missing named parameter '", name, "'"].concat(),
+                )),
+                (None, None) => positional
+                    .next()
+                    .map_or(Ok(Argument::Null), |value| Argument::from_js(ctx, value)),
+            })
+            .collect()
+    }
+}
+
+/// Throws a `RangeError` unless `given` positional values bind `expected` parameters.
+fn check_count(ctx: &Ctx<'_>, given: usize, expected: usize) -> Result<()> {
+    let message = match given.cmp(&expected) {
+        Ordering::Equal => return Ok(()),
+        Ordering::Less => "Too few parameter values were provided, expected ",
+        Ordering::Greater => "Too many parameter values were provided, expected ",
+    };
+    Err(Exception::throw_range(
+        ctx,
+        &[
+            message,
+            &expected.to_string(),
+            " but got ",
+            &given.to_string(),
+        ]
+        .concat(),
+    ))
+}
+
+fn is_id_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80
+}
+
+fn matches_key(name: &str, key: &str) -> bool {
+    if name == key {
+        return true;
+    }
+    match key.as_bytes().first() {
+        Some(b'?' | b':' | b'@' | b'$') => false,
+        _ => &name[1..] == key,
+    }
+}
+
+pub(crate) fn is_plain_object<'js>(ctx: &Ctx<'js>, value: &Value<'js>) -> Result<bool> {
+    let Some(object) = value.as_object() else {
+        return Ok(false);
+    };
+    if value.is_array() || value.is_function() {
+        return Ok(false);
+    }
+    let Some(prototype) = object.get_prototype() else {
+        return Ok(true);
+    };
+    let object_prototype: Object = ctx.globals().get::<_, Object>("Object")?.get("prototype")?;
+    Ok(prototype == object_prototype)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Parameters;
+
+    fn parameters(names: &[Option<&str>]) -> Parameters {
+        Parameters::new(names.iter().map(|name| name.map(str::to_owned)).collect())
+    }
+
+    #[test]
+    fn test_sql_anonymous() {
+        let params = parameters(&[None, Some("?3")]);
+        let sql = "SELECT * FROM test WHERE a = ? AND b = ?3";
+        assert!(!params.is_named());
+        assert_eq!(params.sql(sql), sql);
+    }
+
+    #[test]
+    fn test_sql_named() {
+        let sql = "SELECT ':skip', \"$skip\", a$b FROM t -- @skip\nWHERE a = :a AND b = @b AND c = ? AND d = :a AND e = ?5 AND f = $1";
+        let params = parameters(&[Some(":a"), Some("@b"), None, None, Some("?5"), Some("$1")]);
+        assert!(params.is_named());
+        assert_eq!(
+            params.sql(sql),
+            "SELECT ':skip', \"$skip\", a$b FROM t -- @skip\nWHERE a = ?1 AND b = ?2 AND c = ? AND d = ?1 AND e = ?5 AND f = ?6"
+        );
+    }
+}
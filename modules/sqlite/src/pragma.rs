@@ -0,0 +1,247 @@
+use std::ffi::CString;
+use std::ptr::{self, NonNull};
+
+use libsqlite3_sys::{SQLITE_OK, sqlite3, sqlite3_exec};
+use rquickjs::{Ctx, Exception, FromJs, Object, Result, Value};
+use sqlx::SqlitePool;
+
+use super::TARGET;
+use super::error::{ResultExt as _, SqliteError};
+use super::registry::Registry;
+use super::row::{RowMode, RowReader};
+use super::value::ReadOptions;
+
+/// Pragmas setting the state of a connection rather than of the database
+/// file, which are set on every connection of the pool.
+const CONNECTION_PRAGMAS: &[&str] = &[
+    "analysis_limit",
+    "automatic_index",
+    "busy_timeout",
+    "cache_size",
+    "cache_spill",
+    "case_sensitive_like",
+    "cell_size_check",
+    "checkpoint_fullfsync",
+    "defer_foreign_keys",
+    "foreign_keys",
+    "fullfsync",
+    "hard_heap_limit",
+    "ignore_check_constraints",
+    "journal_mode",
+    "journal_size_limit",
+    "legacy_alter_table",
+    "locking_mode",
+    "max_page_count",
+    "mmap_size",
+    "query_only",
+    "read_uncommitted",
+    "recursive_triggers",
+    "reverse_unordered_selects",
+    "secure_delete",
+    "soft_heap_limit",
+    "synchronous",
+    "temp_store",
+    "threads",
+    "trusted_schema",
+    "wal_autocheckpoint",
+    "writable_schema",
+];
+
+/// Pragmas acting on the database file or only reading state, which run
+/// once even when given a value.
+const DATABASE_PRAGMAS: &[&str] = &[
+    "application_id",
+    "auto_vacuum",
+    "collation_list",
+    "compile_options",
+    "data_version",
+    "database_list",
+    "encoding",
+    "foreign_key_check",
+    "foreign_key_list",
+    "freelist_count",
+    "function_list",
+    "incremental_vacuum",
+    "index_info",
+    "index_list",
+    "index_xinfo",
+    "integrity_check",
+    "module_list",
+    "optimize",
+    "page_count",
+    "page_size",
+    "pragma_list",
+    "quick_check",
+    "schema_version",
+    "shrink_memory",
+    "table_info",
+    "table_list",
+    "table_xinfo",
+    "user_version",
+    "wal_checkpoint",
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PragmaOptions {
+    pub simple: bool,
+}
+
+impl<'js> FromJs<'js> for PragmaOptions {
+    fn from_js(_ctx: &Ctx<'js>, value: Value<'js>) -> Result<Self> {
+        let default = PragmaOptions::default();
+        let obj = value.get::<Object<'js>>()?;
+        let simple = obj
+            .get::<_, Option<bool>>("simple")?
+            .unwrap_or(default.simple);
+        Ok(Self { simple })
+    }
+}
+
+/// A pragma and the statement running it.
+struct Pragma {
+    name: String,
+    sql: String,
+}
+
+impl Pragma {
+    fn new<'js>(ctx: &Ctx<'js>, name: &str, value: Option<Value<'js>>) -> Result<Self> {
+        let (schema, pragma) = match name.split_once('.') {
+            Some((schema, pragma)) => (Some(schema), pragma),
+            None => (None, name),
+        };
+        if !schema.is_none_or(is_identifier) || !is_identifier(pragma) {
+            return Err(Exception::throw_type(
+                ctx,
+                &["Invalid pragma name '", name, "'"].concat(),
+            ));
+        }
+
+        let mut sql = ["PRAGMA ", name].concat();
+        if let Some(value) = value {
+            sql.push_str(" = ");
+            sql.push_str(&literal(ctx, value)?);
+        }
+        Ok(Self {
+            name: pragma.to_ascii_lowercase(),
+            sql,
+        })
+    }
+
+    fn is_connection_scoped(&self) -> bool {
+        CONNECTION_PRAGMAS.contains(&self.name.as_str())
+    }
+
+    fn is_known(&self) -> bool {
+        self.is_connection_scoped() || DATABASE_PRAGMAS.contains(&self.name.as_str())
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Formats the value of a pragma, which can not be bound as a parameter.
+fn literal<'js>(ctx: &Ctx<'js>, value: Value<'js>) -> Result<String> {
+    if let Some(bool) = value.as_bool() {
+        return Ok((bool as i64).to_string());
+    } else if let Some(int) = value.as_int() {
+        return Ok(int.to_string());
+    } else if let Some(big_int) = value.as_big_int() {
+        return Ok(big_int.clone().to_i64()?.to_string());
+    } else if let Some(float) = value.as_float()
+        && float.is_finite()
+    {
+        return Ok(float.to_string());
+    } else if let Some(string) = value.as_string() {
+        return Ok(["'", &string.to_string()?.replace('\'', "''"), "'"].concat());
+    }
+    Err(Exception::throw_type(
+        ctx,
+        &[
+            "Pragma value of type '",
+            value.type_name(),
+            "' is not supported",
+        ]
+        .concat(),
+    ))
+}
+
+/// Runs a pragma and returns its rows, or the first value with `simple`.
+///
+/// Pragmas setting a connection state are also registered to run on every
+/// other connection of the pool, now and when new ones are opened, replacing
+/// the value they were set to before.
+pub async fn pragma<'js>(
+    ctx: &Ctx<'js>,
+    pool: &SqlitePool,
+    registry: &Registry,
+    name: &str,
+    value: Option<Value<'js>>,
+    options: PragmaOptions,
+    read_options: ReadOptions,
+) -> Result<Value<'js>> {
+    let set = value.is_some();
+    let pragma = Pragma::new(ctx, name, value)?;
+    let mut conn = pool.acquire().await.or_throw_sqlite(ctx)?;
+    let rows = sqlx::query(&pragma.sql)
+        .persistent(false)
+        .fetch_all(&mut *conn)
+        .await
+        .or_throw_sql(ctx, &pragma.sql)?;
+
+    if set && pragma.is_connection_scoped() {
+        let sql = CString::new(pragma.sql.as_str())
+            .map_err(|_| Exception::throw_type(ctx, "Pragma must not contain NUL characters"))?;
+        // Only the last value of a pragma is set on the other connections
+        let key = name.to_ascii_lowercase();
+        registry
+            // SAFETY: The registry locks the handle while installing.
+            .replace(&mut conn, key, move |db| unsafe { exec(db, &sql) })
+            .await
+            .or_throw_sql(ctx, &pragma.sql)?;
+    } else if set && !pragma.is_known() && pool.options().get_max_connections() > 1 {
+        log::warn!(
+            target: TARGET,
+            "PRAGMA {} is not known to be a setting of the connection, it was only run on one connection of the pool",
+            pragma.name
+        );
+    }
+
+    let mut reader = RowReader::new(&[], read_options);
+    if options.simple {
+        reader.set_mode(RowMode::Pluck);
+        return match rows.first() {
+            Some(row) => reader.read(ctx, row),
+            None => Ok(Value::new_undefined(ctx.clone())),
+        };
+    }
+    let array = rquickjs::Array::new(ctx.clone())?;
+    for (index, row) in rows.iter().enumerate() {
+        array.set(index, reader.read(ctx, row)?)?;
+    }
+    Ok(array.into_value())
+}
+
+/// Runs the statements of `sql`, ignoring their results.
+///
+/// # Safety
+/// `db` must be locked while the statements run.
+unsafe fn exec(db: NonNull<sqlite3>, sql: &CString) -> std::result::Result<(), SqliteError> {
+    let code = unsafe {
+        sqlite3_exec(
+            db.as_ptr(),
+            sql.as_ptr(),
+            None,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+    if code != SQLITE_OK {
+        return Err(SqliteError::from_handle(db));
+    }
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::ptr::{self, NonNull};
+
+use libsqlite3_sys::{
+    SQLITE_OK, sqlite3, sqlite3_bind_parameter_count, sqlite3_bind_parameter_name,
+    sqlite3_column_count, sqlite3_column_database_name, sqlite3_column_decltype,
+    sqlite3_column_name, sqlite3_column_origin_name, sqlite3_column_table_name, sqlite3_errmsg,
+    sqlite3_errstr, sqlite3_expanded_sql, sqlite3_finalize, sqlite3_free, sqlite3_open_v2,
+    sqlite3_prepare_v2, sqlite3_stmt,
+};
+use sqlx::SqliteConnection;
+
+use super::Argument;
+use super::error::SqliteError;
+
+/// Metadata of a result column of a statement.
+#[derive(Debug, Clone)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub declared_type: Option<String>,
+    pub database: Option<String>,
+    pub table: Option<String>,
+    pub origin: Option<String>,
+}
+
+/// A statement prepared directly on a raw connection handle, finalized on drop.
+///
+/// sqlx does not give access to the handles of its statements, so statements
+/// are prepared again when their raw API is needed. The connection handle
+/// must stay locked while the statement is alive.
+pub struct RawStatement(NonNull<sqlite3_stmt>);
+
+impl RawStatement {
+    /// Prepares the first statement of `sql`, returns `None` if it is empty.
+    ///
+    /// # Safety
+    /// `db` must be locked for the lifetime of the returned statement.
+    pub unsafe fn prepare(db: NonNull<sqlite3>, sql: &str) -> Result<Option<Self>, sqlx::Error> {
+        let sql = CString::new(sql).map_err(|err| sqlx::Error::Protocol(err.to_string()))?;
+        let mut stmt: *mut sqlite3_stmt = ptr::null_mut();
+        unsafe {
+            let code =
+                sqlite3_prepare_v2(db.as_ptr(), sql.as_ptr(), -1, &mut stmt, ptr::null_mut());
+            if code != SQLITE_OK {
+                return Err(sqlx::Error::Configuration(
+                    SqliteError::from_handle(db).into(),
+                ));
+            }
+        }
+        Ok(NonNull::new(stmt).map(Self))
+    }
+
+    pub fn columns(&self) -> Vec<ColumnInfo> {
+        let stmt = self.0.as_ptr();
+        // SAFETY: The statement is valid until dropped.
+        unsafe {
+            (0..sqlite3_column_count(stmt))
+                .map(|index| ColumnInfo {
+                    name: text(sqlite3_column_name(stmt, index)).unwrap_or_default(),
+                    declared_type: text(sqlite3_column_decltype(stmt, index)),
+                    database: text(sqlite3_column_database_name(stmt, index)),
+                    table: text(sqlite3_column_table_name(stmt, index)),
+                    origin: text(sqlite3_column_origin_name(stmt, index)),
+                })
+                .collect()
+        }
+    }
+
+    /// The name of each parameter, `None` for anonymous ones.
+    pub fn parameters(&self) -> Vec<Option<String>> {
+        let stmt = self.0.as_ptr();
+        // SAFETY: The statement is valid until dropped.
+        unsafe {
+            (1..=sqlite3_bind_parameter_count(stmt))
+                .map(|index| text(sqlite3_bind_parameter_name(stmt, index)))
+                .collect()
+        }
+    }
+
+    pub fn as_ptr(&self) -> *mut sqlite3_stmt {
+        self.0.as_ptr()
+    }
+
+    /// Binds the arguments in order, starting at index 1.
+    pub fn bind(&self, arguments: &[Argument<'_>]) -> Result<(), sqlx::Error> {
+        for (index, argument) in arguments.iter().enumerate() {
+            // SAFETY: The statement is valid until dropped, values are copied by SQLite.
+            let code = unsafe { argument.bind_raw(self.0.as_ptr(), index as c_int + 1) };
+            if code != SQLITE_OK {
+                return Err(sqlx::Error::Protocol(
+                    ["Unable to bind parameter ", &(index + 1).to_string()].concat(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The SQL of the statement with its bound parameters expanded.
+    pub fn expanded_sql(&self) -> Option<String> {
+        // SAFETY: The statement is valid until dropped, the string is freed after copy.
+        unsafe {
+            let sql = sqlite3_expanded_sql(self.0.as_ptr());
+            let expanded = text(sql);
+            sqlite3_free(sql as *mut _);
+            expanded
+        }
+    }
+}
+
+impl Drop for RawStatement {
+    fn drop(&mut self) {
+        // SAFETY: The statement is finalized once.
+        unsafe { sqlite3_finalize(self.0.as_ptr()) };
+    }
+}
+
+unsafe extern "C" {
+    // Not part of the bindings of libsqlite3-sys
+    fn sqlite3_close_v2(db: *mut sqlite3) -> c_int;
+}
+
+/// A database connection opened outside of the pool, closed on drop.
+pub struct RawConnection(NonNull<sqlite3>);
+
+impl RawConnection {
+    pub fn open(path: &CStr, flags: c_int) -> Result<Self, SqliteError> {
+        let mut db: *mut sqlite3 = ptr::null_mut();
+        // SAFETY: The connection is closed on drop, even if opening failed.
+        unsafe {
+            let code = sqlite3_open_v2(path.as_ptr(), &mut db, flags, ptr::null());
+            let db = NonNull::new(db).map(Self);
+            if code != SQLITE_OK {
+                return Err(match &db {
+                    Some(db) => SqliteError::from_handle(db.0),
+                    None => SqliteError::from_code(code),
+                });
+            }
+            db.ok_or_else(|| SqliteError::from_code(code))
+        }
+    }
+
+    pub fn as_raw_handle(&self) -> NonNull<sqlite3> {
+        self.0
+    }
+}
+
+impl Drop for RawConnection {
+    fn drop(&mut self) {
+        // SAFETY: The connection is closed once, it is only freed once the
+        // statements still prepared on it are finalized.
+        unsafe { sqlite3_close_v2(self.0.as_ptr()) };
+    }
+}
+
+/// The description of a result code.
+pub fn error_string(code: c_int) -> String {
+    // SAFETY: sqlite3_errstr always returns a static string.
+    unsafe { text(sqlite3_errstr(code)) }.unwrap_or_default()
+}
+
+/// The message of the last error of a connection.
+pub fn error_message(db: NonNull<sqlite3>) -> String {
+    // SAFETY: The message is copied before the connection is used again.
+    unsafe { text(sqlite3_errmsg(db.as_ptr())) }.unwrap_or_default()
+}
+
+/// Reads the parameter names and the metadata of the result columns of the
+/// first statement of `sql`.
+pub async fn describe(
+    conn: &mut SqliteConnection,
+    sql: &str,
+) -> Result<(Vec<Option<String>>, Vec<ColumnInfo>), sqlx::Error> {
+    let mut handle = conn.lock_handle().await?;
+    // SAFETY: The handle is locked until the statement is dropped.
+    let stmt = unsafe { RawStatement::prepare(handle.as_raw_handle(), sql)? };
+    Ok(stmt
+        .map(|stmt| (stmt.parameters(), stmt.columns()))
+        .unwrap_or_default())
+}
+
+/// Expands the parameters of the first statement of `sql`.
+pub async fn expanded_sql(
+    conn: &mut SqliteConnection,
+    sql: &str,
+    arguments: &[Argument<'_>],
+) -> Result<Option<String>, sqlx::Error> {
+    let mut handle = conn.lock_handle().await?;
+    // SAFETY: The handle is locked until the statement is dropped.
+    let Some(stmt) = (unsafe { RawStatement::prepare(handle.as_raw_handle(), sql)? }) else {
+        return Ok(None);
+    };
+    stmt.bind(arguments)?;
+    Ok(stmt.expanded_sql())
+}
+
+unsafe fn text(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    Some(
+        unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
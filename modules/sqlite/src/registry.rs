@@ -0,0 +1,190 @@
+use std::ffi::c_void;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+
+use libsqlite3_sys::{SQLITE_ERROR, sqlite3, sqlite3_get_clientdata, sqlite3_set_clientdata};
+use sqlx::SqliteConnection;
+
+use super::error::SqliteError;
+
+type Install = dyn Fn(NonNull<sqlite3>) -> Result<(), SqliteError> + Send + Sync;
+
+const INSTALLED_KEY: &std::ffi::CStr = c"rquickjs_extra_sqlite_installed";
+
+/// Definitions (functions, collations, hooks, ...) that must be installed on
+/// every connection of a pool.
+///
+/// Definitions are numbered in the order they are added and connections keep
+/// track of the last one they installed, so definitions added after a
+/// connection was opened are installed the next time it is acquired.
+#[derive(Clone, Default)]
+pub struct Registry {
+    definitions: Arc<Mutex<Definitions>>,
+}
+
+#[derive(Default)]
+struct Definitions {
+    entries: Vec<Definition>,
+    /// The number of the last definition added.
+    last: usize,
+}
+
+#[derive(Clone)]
+struct Definition {
+    number: usize,
+    /// Identifies a definition replaced when a new one is added with the same key.
+    key: Option<String>,
+    install: Arc<Install>,
+}
+
+impl Registry {
+    /// Installs the definitions missing on a connection.
+    pub async fn install(&self, conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+        let mut handle = conn.lock_handle().await?;
+        self.install_missing(handle.as_raw_handle())
+            .map_err(|err| sqlx::Error::Configuration(err.into()))
+    }
+
+    /// Installs the definitions missing on a locked connection handle.
+    fn install_missing(&self, db: NonNull<sqlite3>) -> Result<(), SqliteError> {
+        let (entries, last) = {
+            let definitions = self.definitions.lock().unwrap();
+            (definitions.entries.clone(), definitions.last)
+        };
+        // SAFETY: The handle is locked so the worker thread is not using it.
+        let installed = unsafe { installed(db) };
+        if installed >= last {
+            return Ok(());
+        }
+        for definition in entries.iter().filter(|entry| entry.number > installed) {
+            (definition.install)(db)?;
+        }
+        // SAFETY: The handle is locked so the worker thread is not using it.
+        unsafe { set_installed(db, last) };
+        Ok(())
+    }
+
+    /// Installs a new definition on a connection and adds it to the registry
+    /// if it succeeded, so invalid definitions never reach the other connections.
+    pub async fn add<F>(&self, conn: &mut SqliteConnection, install: F) -> Result<(), SqliteError>
+    where
+        F: Fn(NonNull<sqlite3>) -> Result<(), SqliteError> + Send + Sync + 'static,
+    {
+        self.insert(conn, None, Arc::new(install)).await
+    }
+
+    /// Like [`Registry::add`], replacing the definition added with the same
+    /// `key` so connections that did not install it yet only install the new one.
+    pub async fn replace<F>(
+        &self,
+        conn: &mut SqliteConnection,
+        key: String,
+        install: F,
+    ) -> Result<(), SqliteError>
+    where
+        F: Fn(NonNull<sqlite3>) -> Result<(), SqliteError> + Send + Sync + 'static,
+    {
+        self.insert(conn, Some(key), Arc::new(install)).await
+    }
+
+    async fn insert(
+        &self,
+        conn: &mut SqliteConnection,
+        key: Option<String>,
+        install: Arc<Install>,
+    ) -> Result<(), SqliteError> {
+        let mut handle = conn.lock_handle().await.map_err(|err| {
+            SqliteError::from_sqlx(&err)
+                .unwrap_or_else(|| SqliteError::new(SQLITE_ERROR, err.to_string()))
+        })?;
+        let db = handle.as_raw_handle();
+        self.install_missing(db)?;
+        install(db)?;
+
+        let mut definitions = self.definitions.lock().unwrap();
+        if key.is_some() {
+            definitions.entries.retain(|entry| entry.key != key);
+        }
+        let previous = definitions.last;
+        definitions.last += 1;
+        let number = definitions.last;
+        definitions.entries.push(Definition {
+            number,
+            key,
+            install,
+        });
+        // SAFETY: The handle is locked so the worker thread is not using it.
+        unsafe {
+            if installed(db) == previous {
+                set_installed(db, number);
+            }
+        }
+        Ok(())
+    }
+}
+
+unsafe fn installed(db: NonNull<sqlite3>) -> usize {
+    let data = unsafe { sqlite3_get_clientdata(db.as_ptr(), INSTALLED_KEY.as_ptr()) };
+    if data.is_null() {
+        0
+    } else {
+        unsafe { *(data as *const usize) }
+    }
+}
+
+unsafe fn set_installed(db: NonNull<sqlite3>, count: usize) {
+    unsafe extern "C" fn destroy(data: *mut c_void) {
+        drop(unsafe { Box::from_raw(data as *mut usize) });
+    }
+
+    let data = Box::into_raw(Box::new(count));
+    unsafe {
+        sqlite3_set_clientdata(
+            db.as_ptr(),
+            INSTALLED_KEY.as_ptr(),
+            data as *mut c_void,
+            Some(destroy),
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use sqlx::{Connection, SqliteConnection};
+
+    use super::Registry;
+
+    #[tokio::test]
+    async fn test_registry_replace() {
+        let registry = Registry::default();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let record = |value: &'static str| {
+            let calls = calls.clone();
+            move |_| {
+                calls.lock().unwrap().push(value);
+                Ok(())
+            }
+        };
+
+        let mut first = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        for value in ["a", "b", "c"] {
+            registry
+                .replace(&mut first, "key".into(), record(value))
+                .await
+                .unwrap();
+        }
+        registry.add(&mut first, record("other")).await.unwrap();
+        assert_eq!(*calls.lock().unwrap(), ["a", "b", "c", "other"]);
+
+        // A new connection only installs the last definition of a key
+        let mut second = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        registry.install(&mut second).await.unwrap();
+        registry.install(&mut second).await.unwrap();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["a", "b", "c", "other", "c", "other"]
+        );
+    }
+}
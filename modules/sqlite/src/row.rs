@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use rquickjs::{Array, Ctx, Object, Result};
+use sqlx::Column as _;
+use sqlx::Row as _;
+use sqlx::sqlite::{SqliteColumn, SqliteRow};
+
+use super::Value;
+use super::raw::ColumnInfo;
+use super::value::{DeclaredType, ReadOptions};
+
+/// The shape of the rows returned by a statement.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RowMode {
+    /// An object keyed by column name.
+    #[default]
+    Object,
+    /// An array of values in column order.
+    Array,
+    /// The value of the first column.
+    Pluck,
+}
+
+/// Converts the rows of a statement to JS values.
+#[derive(Debug, Clone, Default)]
+pub struct RowReader {
+    types: Arc<[DeclaredType]>,
+    options: ReadOptions,
+    mode: RowMode,
+}
+
+impl RowReader {
+    pub fn new(columns: &[ColumnInfo], options: ReadOptions) -> Self {
+        let types = columns
+            .iter()
+            .map(|column| DeclaredType::parse(column.declared_type.as_deref()))
+            .collect();
+        Self {
+            types,
+            options,
+            mode: RowMode::default(),
+        }
+    }
+
+    pub fn options(&self) -> ReadOptions {
+        self.options
+    }
+
+    pub fn set_options(&mut self, options: ReadOptions) {
+        self.options = options;
+    }
+
+    pub fn mode(&self) -> RowMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: RowMode) {
+        self.mode = mode;
+    }
+
+    fn declared_type(&self, ordinal: usize) -> DeclaredType {
+        if !self.options.declared_types {
+            return DeclaredType::Other;
+        }
+        self.types
+            .get(ordinal)
+            .copied()
+            .unwrap_or(DeclaredType::Other)
+    }
+
+    pub fn read<'js>(&self, ctx: &Ctx<'js>, row: &SqliteRow) -> Result<rquickjs::Value<'js>> {
+        match self.mode {
+            RowMode::Object => {
+                let obj = Object::new(ctx.clone())?;
+                for column in row.columns() {
+                    obj.set(column.name(), self.read_column(ctx, row, column)?)?;
+                }
+                Ok(obj.into_value())
+            }
+            RowMode::Array => {
+                let array = Array::new(ctx.clone())?;
+                for column in row.columns() {
+                    array.set(column.ordinal(), self.read_column(ctx, row, column)?)?;
+                }
+                Ok(array.into_value())
+            }
+            RowMode::Pluck => match row.columns().first() {
+                Some(column) => self.read_column(ctx, row, column),
+                None => Ok(rquickjs::Value::new_undefined(ctx.clone())),
+            },
+        }
+    }
+
+    fn read_column<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        row: &SqliteRow,
+        column: &SqliteColumn,
+    ) -> Result<rquickjs::Value<'js>> {
+        let value = Value::try_read(ctx, column, row)?;
+        let declared = self.declared_type(column.ordinal());
+        value.into_js_typed(ctx, declared, self.options.safe_integers)
+    }
+}
@@ -0,0 +1,65 @@
+use std::ffi::c_void;
+use std::slice;
+
+use libsqlite3_sys::{
+    SQLITE_DESERIALIZE_READONLY, SQLITE_OK, SQLITE_OPEN_MEMORY, SQLITE_OPEN_READWRITE,
+    sqlite3_deserialize, sqlite3_free, sqlite3_int64, sqlite3_serialize,
+};
+use rquickjs::{Ctx, Result, TypedArray};
+use sqlx::SqlitePool;
+
+use super::backup;
+use super::error::{ResultExt as _, SqliteError};
+use super::raw::RawConnection;
+
+/// Copies the main database of the pool into a `Uint8Array`.
+pub async fn serialize<'js>(ctx: &Ctx<'js>, pool: &SqlitePool) -> Result<TypedArray<'js, u8>> {
+    let mut conn = pool.acquire().await.or_throw_sqlite(ctx)?;
+    let mut handle = conn.lock_handle().await.or_throw_sqlite(ctx)?;
+    let db = handle.as_raw_handle();
+
+    let mut size: sqlite3_int64 = 0;
+    // SAFETY: The handle is locked, the image is copied before being freed.
+    unsafe {
+        let data = sqlite3_serialize(db.as_ptr(), c"main".as_ptr(), &mut size, 0);
+        if data.is_null() {
+            return Err(SqliteError::from_handle(db)
+                .context("Unable to serialize database")
+                .throw(ctx));
+        }
+        let array = TypedArray::new_copy(ctx.clone(), slice::from_raw_parts(data, size as usize));
+        sqlite3_free(data as *mut c_void);
+        array
+    }
+}
+
+/// Replaces the main database of the pool with a serialized image.
+///
+/// The image is loaded read-only in a private in-memory connection, without
+/// copy, then copied to the pool database so every connection sees it.
+pub async fn deserialize(ctx: &Ctx<'_>, pool: &SqlitePool, data: &[u8]) -> Result<()> {
+    let mut conn = pool.acquire().await.or_throw_sqlite(ctx)?;
+    let mut handle = conn.lock_handle().await.or_throw_sqlite(ctx)?;
+
+    let source = RawConnection::open(c":memory:", SQLITE_OPEN_READWRITE | SQLITE_OPEN_MEMORY)
+        .map_err(|err| err.context("Unable to deserialize database").throw(ctx))?;
+    // SAFETY: SQLite does not write to or free read-only images, and the
+    // source connection is closed before `data` is released.
+    unsafe {
+        let code = sqlite3_deserialize(
+            source.as_raw_handle().as_ptr(),
+            c"main".as_ptr(),
+            data.as_ptr() as *mut u8,
+            data.len() as sqlite3_int64,
+            data.len() as sqlite3_int64,
+            SQLITE_DESERIALIZE_READONLY as _,
+        );
+        if code != SQLITE_OK {
+            return Err(SqliteError::from_handle(source.as_raw_handle())
+                .context("Unable to deserialize database")
+                .throw(ctx));
+        }
+        backup::copy(handle.as_raw_handle(), source.as_raw_handle())
+            .map_err(|err| err.context("Unable to deserialize database").throw(ctx))
+    }
+}
@@ -0,0 +1,689 @@
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::ptr::{self, NonNull};
+use std::slice;
+use std::sync::Arc;
+
+use libsqlite3_sys::{
+    SQLITE_ABORT, SQLITE_CHANGESET_ABORT, SQLITE_CHANGESET_CONFLICT, SQLITE_CHANGESET_CONSTRAINT,
+    SQLITE_CHANGESET_DATA, SQLITE_CHANGESET_FOREIGN_KEY, SQLITE_CHANGESET_NOTFOUND,
+    SQLITE_CHANGESET_OMIT, SQLITE_CHANGESET_REPLACE, SQLITE_NOMEM, SQLITE_OK, sqlite3,
+    sqlite3_free,
+};
+use rquickjs::{
+    Ctx, Exception, FromJs, Function, JsLifetime, Object, Result, TypedArray, Value, class::Trace,
+    function::Opt,
+};
+use sqlx::pool::PoolConnection;
+use sqlx::{Sqlite, SqlitePool};
+use tokio::sync::Mutex;
+
+use super::Statement;
+use super::callback::{self, Call, Callback, Caller, Dispatcher};
+use super::cancel::QueryOptions;
+use super::connection::{Connection, Iterating};
+use super::error::{ResultExt as _, SqliteError};
+use super::value::ReadOptions;
+
+/// Bindings of the session extension, which libsqlite3-sys only generates
+/// with bindgen. The bundled SQLite must be compiled with
+/// `SQLITE_ENABLE_SESSION` and `SQLITE_ENABLE_PREUPDATE_HOOK`.
+mod ffi {
+    use std::ffi::{c_char, c_int, c_void};
+
+    use libsqlite3_sys::sqlite3;
+
+    #[repr(C)]
+    pub struct sqlite3_session {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    pub struct sqlite3_changeset_iter {
+        _private: [u8; 0],
+    }
+
+    pub type Filter = unsafe extern "C" fn(ctx: *mut c_void, table: *const c_char) -> c_int;
+    pub type Conflict = unsafe extern "C" fn(
+        ctx: *mut c_void,
+        conflict: c_int,
+        iter: *mut sqlite3_changeset_iter,
+    ) -> c_int;
+
+    unsafe extern "C" {
+        pub fn sqlite3session_create(
+            db: *mut sqlite3,
+            database: *const c_char,
+            session: *mut *mut sqlite3_session,
+        ) -> c_int;
+        pub fn sqlite3session_delete(session: *mut sqlite3_session);
+        pub fn sqlite3session_attach(session: *mut sqlite3_session, table: *const c_char) -> c_int;
+        pub fn sqlite3session_changeset(
+            session: *mut sqlite3_session,
+            size: *mut c_int,
+            changeset: *mut *mut c_void,
+        ) -> c_int;
+        pub fn sqlite3session_patchset(
+            session: *mut sqlite3_session,
+            size: *mut c_int,
+            patchset: *mut *mut c_void,
+        ) -> c_int;
+        pub fn sqlite3changeset_apply(
+            db: *mut sqlite3,
+            size: c_int,
+            changeset: *mut c_void,
+            filter: Option<Filter>,
+            conflict: Option<Conflict>,
+            ctx: *mut c_void,
+        ) -> c_int;
+        pub fn sqlite3changeset_op(
+            iter: *mut sqlite3_changeset_iter,
+            table: *mut *const c_char,
+            columns: *mut c_int,
+            op: *mut c_int,
+            indirect: *mut c_int,
+        ) -> c_int;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SessionOptions {
+    pub tables: Option<Vec<String>>,
+    pub database: Option<String>,
+}
+
+impl<'js> FromJs<'js> for SessionOptions {
+    fn from_js(_ctx: &Ctx<'js>, value: Value<'js>) -> Result<Self> {
+        let obj = value.get::<Object<'js>>()?;
+        Ok(Self {
+            tables: obj.get("tables")?,
+            database: obj.get("database")?,
+        })
+    }
+}
+
+#[derive(Debug, Default, JsLifetime)]
+pub struct ApplyOptions<'js> {
+    pub filter: Option<Function<'js>>,
+    pub on_conflict: Option<Function<'js>>,
+}
+
+impl<'js> FromJs<'js> for ApplyOptions<'js> {
+    fn from_js(_ctx: &Ctx<'js>, value: Value<'js>) -> Result<Self> {
+        let obj = value.get::<Object<'js>>()?;
+        Ok(Self {
+            filter: obj.get("filter")?,
+            on_conflict: obj.get("onConflict")?,
+        })
+    }
+}
+
+/// A session object, deleted on drop.
+struct RawSession(NonNull<ffi::sqlite3_session>);
+
+// SAFETY: The session is only used while the handle of its connection is
+// locked, whichever thread it is on.
+unsafe impl Send for RawSession {}
+
+impl Drop for RawSession {
+    fn drop(&mut self) {
+        // SAFETY: The session is deleted once, before its connection is
+        // released, and deleting it does not use the connection.
+        unsafe { ffi::sqlite3session_delete(self.0.as_ptr()) };
+    }
+}
+
+/// The connection pinned by a session, with the session recording it.
+pub struct SessionState {
+    // Dropped first, a session must be deleted before its connection is released
+    session: Option<RawSession>,
+    conn: Option<PoolConnection<Sqlite>>,
+}
+
+impl SessionState {
+    pub fn connection(&mut self) -> Option<&mut PoolConnection<Sqlite>> {
+        self.conn.as_mut()
+    }
+}
+
+#[derive(Clone, Trace, JsLifetime)]
+#[rquickjs::class]
+pub struct Session {
+    #[qjs(skip_trace)]
+    state: Arc<Mutex<SessionState>>,
+    #[qjs(skip_trace)]
+    iterating: Iterating,
+    #[qjs(skip_trace)]
+    options: ReadOptions,
+}
+
+impl Session {
+    /// Starts recording the changes made through a dedicated connection of the pool.
+    pub async fn start(
+        ctx: &Ctx<'_>,
+        pool: &SqlitePool,
+        options: SessionOptions,
+        read_options: ReadOptions,
+    ) -> Result<Self> {
+        let database = to_cstring(ctx, options.database.as_deref().unwrap_or("main"))?;
+        let tables = match options.tables {
+            Some(tables) => Some(
+                tables
+                    .iter()
+                    .map(|table| to_cstring(ctx, table))
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            None => None,
+        };
+
+        let mut conn = pool.acquire().await.or_throw_sqlite(ctx)?;
+        let session = {
+            let mut handle = conn.lock_handle().await.or_throw_sqlite(ctx)?;
+            let db = handle.as_raw_handle();
+            // SAFETY: The handle is locked while the session is created and attached.
+            unsafe { create(db, &database, tables.as_deref()) }
+                .map_err(|err| err.context("Unable to create session").throw(ctx))?
+        };
+
+        let state = SessionState {
+            session: Some(session),
+            conn: Some(conn),
+        };
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+            iterating: Iterating::default(),
+            options: read_options,
+        })
+    }
+
+    fn connection(&self) -> Connection {
+        Connection::Session {
+            state: self.state.clone(),
+            iterating: self.iterating.clone(),
+        }
+    }
+
+    async fn export<'js>(&self, ctx: &Ctx<'js>, patchset: bool) -> Result<TypedArray<'js, u8>> {
+        let mut state = self.iterating.lock(ctx, &self.state).await?;
+        let SessionState {
+            session: Some(session),
+            conn: Some(conn),
+        } = &mut *state
+        else {
+            return Err(Exception::throw_message(ctx, "Session is closed"));
+        };
+        let _handle = conn.lock_handle().await.or_throw_sqlite(ctx)?;
+
+        let mut size: c_int = 0;
+        let mut data: *mut c_void = ptr::null_mut();
+        // SAFETY: The handle is locked, the changeset is copied before being freed.
+        unsafe {
+            let code = if patchset {
+                ffi::sqlite3session_patchset(session.0.as_ptr(), &mut size, &mut data)
+            } else {
+                ffi::sqlite3session_changeset(session.0.as_ptr(), &mut size, &mut data)
+            };
+            if code != SQLITE_OK {
+                return Err(SqliteError::from_code(code)
+                    .context("Unable to export changes")
+                    .throw(ctx));
+            }
+            let bytes = if data.is_null() {
+                &[]
+            } else {
+                slice::from_raw_parts(data as *const u8, size as usize)
+            };
+            let array = TypedArray::new_copy(ctx.clone(), bytes);
+            sqlite3_free(data);
+            array
+        }
+    }
+}
+
+#[rquickjs::methods(rename_all = "camelCase")]
+impl Session {
+    async fn exec<'js>(&self, ctx: Ctx<'js>, sql: String, options: Opt<Value<'js>>) -> Result<()> {
+        let options = QueryOptions::from_js(&ctx, options.0)?;
+        let mut conn = self.connection().acquire(&ctx).await?;
+        let abort = options.start(&ctx, &mut conn).await?;
+        let result = sqlx::raw_sql(&sql).execute(&mut *conn).await;
+        abort.finish(&ctx, &mut conn, result, &sql).await?;
+        Ok(())
+    }
+
+    async fn prepare(&self, ctx: Ctx<'_>, sql: String) -> Result<Statement> {
+        Statement::prepare(&ctx, self.connection(), &sql, self.options).await
+    }
+
+    /// Returns the changes recorded so far as a changeset.
+    async fn changeset<'js>(&self, ctx: Ctx<'js>) -> Result<TypedArray<'js, u8>> {
+        self.export(&ctx, false).await
+    }
+
+    /// Returns the changes recorded so far as a patchset, which omits the
+    /// original values of updated and deleted rows.
+    async fn patchset<'js>(&self, ctx: Ctx<'js>) -> Result<TypedArray<'js, u8>> {
+        self.export(&ctx, true).await
+    }
+
+    /// Stops recording and releases the connection of the session.
+    async fn close(&self, ctx: Ctx<'_>) -> Result<()> {
+        let mut state = self.iterating.lock(&ctx, &self.state).await?;
+        if let Some(mut conn) = state.conn.take() {
+            let _handle = conn.lock_handle().await.or_throw_sqlite(&ctx)?;
+            state.session.take();
+        }
+        Ok(())
+    }
+}
+
+/// Creates a session on `database`, recording `tables` or every table.
+///
+/// # Safety
+/// `db` must be locked while the session is created.
+unsafe fn create(
+    db: NonNull<sqlite3>,
+    database: &CStr,
+    tables: Option<&[CString]>,
+) -> std::result::Result<RawSession, SqliteError> {
+    let mut session: *mut ffi::sqlite3_session = ptr::null_mut();
+    let code = unsafe { ffi::sqlite3session_create(db.as_ptr(), database.as_ptr(), &mut session) };
+    if code != SQLITE_OK {
+        return Err(SqliteError::from_handle(db));
+    }
+    let session =
+        RawSession(NonNull::new(session).ok_or_else(|| SqliteError::from_code(SQLITE_NOMEM))?);
+
+    let tables = match tables {
+        Some(tables) => tables.iter().map(|table| table.as_ptr()).collect(),
+        None => vec![ptr::null()],
+    };
+    for table in tables {
+        let code = unsafe { ffi::sqlite3session_attach(session.0.as_ptr(), table) };
+        if code != SQLITE_OK {
+            return Err(SqliteError::from_handle(db));
+        }
+    }
+    Ok(session)
+}
+
+/// Calls the filter of a changeset being applied, on the JS context.
+pub fn filter<'js>(
+    ctx: &Ctx<'js>,
+    options: Option<&ApplyOptions<'js>>,
+    table: &str,
+) -> Result<bool> {
+    let options = options.ok_or_else(|| Exception::throw_message(ctx, "Unknown changeset"))?;
+    match &options.filter {
+        Some(filter) => filter.call((table,)),
+        None => Ok(true),
+    }
+}
+
+/// Calls the conflict handler of a changeset being applied, on the JS
+/// context, and converts its resolution.
+pub fn conflict<'js>(
+    ctx: &Ctx<'js>,
+    options: Option<&ApplyOptions<'js>>,
+    conflict: c_int,
+    table: Option<&str>,
+) -> Result<c_int> {
+    let options = options.ok_or_else(|| Exception::throw_message(ctx, "Unknown changeset"))?;
+    let Some(on_conflict) = &options.on_conflict else {
+        return Ok(SQLITE_CHANGESET_ABORT);
+    };
+    let kind = match conflict {
+        SQLITE_CHANGESET_DATA => "data",
+        SQLITE_CHANGESET_NOTFOUND => "notFound",
+        SQLITE_CHANGESET_CONFLICT => "conflict",
+        SQLITE_CHANGESET_CONSTRAINT => "constraint",
+        SQLITE_CHANGESET_FOREIGN_KEY => "foreignKey",
+        _ => return Ok(SQLITE_CHANGESET_ABORT),
+    };
+    let action: String = on_conflict.call((kind, table))?;
+    match action.as_str() {
+        "omit" => Ok(SQLITE_CHANGESET_OMIT),
+        "abort" => Ok(SQLITE_CHANGESET_ABORT),
+        "replace" if conflict == SQLITE_CHANGESET_DATA || conflict == SQLITE_CHANGESET_CONFLICT => {
+            Ok(SQLITE_CHANGESET_REPLACE)
+        }
+        "replace" => Err(Exception::throw_type(
+            ctx,
+            &[
+                "Cannot replace on a ",
+                kind,
+                " conflict, only data and conflict conflicts can be replaced",
+            ]
+            .concat(),
+        )),
+        _ => Err(Exception::throw_type(
+            ctx,
+            &[
+                "Invalid conflict resolution '",
+                &action,
+                "', expected 'omit', 'replace' or 'abort'",
+            ]
+            .concat(),
+        )),
+    }
+}
+
+/// A changeset being applied on a blocking thread, given to the callbacks
+/// of SQLite which call the JS handlers `id` through the dispatcher.
+struct Applying {
+    id: usize,
+    db: usize,
+    caller: Caller,
+    on_conflict: bool,
+    /// Set once a handler failed, which aborts the changeset.
+    failed: bool,
+}
+
+/// Applies a changeset or patchset to the main database of the pool.
+/// Returns `false` if it was aborted by the conflict handler.
+///
+/// SQLite runs the changeset on a blocking thread, like statements run on
+/// the worker thread of their connection, so the handlers and the functions
+/// called by triggers can run on the JS context meanwhile.
+pub async fn apply<'js>(
+    ctx: &Ctx<'js>,
+    pool: &SqlitePool,
+    dispatcher: &Dispatcher,
+    changeset: Vec<u8>,
+    options: ApplyOptions<'js>,
+) -> Result<bool> {
+    let id = dispatcher.next_id();
+    let applying = Applying {
+        id,
+        db: 0,
+        caller: dispatcher.caller(ctx),
+        on_conflict: options.on_conflict.is_some(),
+        failed: false,
+    };
+    let filter = options.filter.is_some();
+    dispatcher.register(ctx, id, Callback::Changeset(options));
+    let result = run(ctx, pool, changeset, filter, applying).await;
+    dispatcher.unregister(id);
+    result
+}
+
+async fn run(
+    ctx: &Ctx<'_>,
+    pool: &SqlitePool,
+    mut changeset: Vec<u8>,
+    filter: bool,
+    mut applying: Applying,
+) -> Result<bool> {
+    let size = c_int::try_from(changeset.len())
+        .map_err(|_| Exception::throw_range(ctx, "Changeset is too large"))?;
+    let mut conn = pool.acquire().await.or_throw_sqlite(ctx)?;
+    let db = conn
+        .lock_handle()
+        .await
+        .or_throw_sqlite(ctx)?
+        .as_raw_handle();
+    applying.db = db.as_ptr() as usize;
+
+    callback::watch(ctx, db);
+    // The connection moves to the thread so it stays locked until SQLite is done
+    let task = tokio::task::spawn_blocking(move || {
+        let result = futures::executor::block_on(conn.lock_handle()).map(|mut handle| {
+            let db = handle.as_raw_handle();
+            // SAFETY: The handle is locked, SQLite does not modify the
+            // changeset and `applying` outlives the call.
+            let code = unsafe {
+                ffi::sqlite3changeset_apply(
+                    db.as_ptr(),
+                    size,
+                    changeset.as_mut_ptr() as *mut c_void,
+                    filter.then_some(call_filter as ffi::Filter),
+                    Some(call_conflict),
+                    &mut applying as *mut Applying as *mut c_void,
+                )
+            };
+            match code {
+                SQLITE_OK => Ok(true),
+                SQLITE_ABORT => Ok(false),
+                _ => Err(SqliteError::from_handle(db)),
+            }
+        });
+        (conn, result)
+    })
+    .await;
+    let failure = callback::unwatch(ctx, db);
+
+    let (_conn, result) =
+        task.map_err(|_| Exception::throw_message(ctx, "Unable to apply changeset"))?;
+    if let Some(err) = failure {
+        return Err(err);
+    }
+    result
+        .or_throw_sqlite(ctx)?
+        .map_err(|err| err.context("Unable to apply changeset").throw(ctx))
+}
+
+unsafe extern "C" fn call_filter(data: *mut c_void, table: *const c_char) -> c_int {
+    // SAFETY: `data` is the changeset given to sqlite3changeset_apply.
+    let applying = unsafe { &mut *(data as *mut Applying) };
+    if applying.failed {
+        return 0;
+    }
+    let table = unsafe { CStr::from_ptr(table) }
+        .to_string_lossy()
+        .into_owned();
+    let apply = applying.caller.call(|done| Call::Filter {
+        id: applying.id,
+        db: applying.db,
+        table,
+        done,
+    });
+    match apply.flatten() {
+        Some(apply) => apply as c_int,
+        None => {
+            applying.failed = true;
+            0
+        }
+    }
+}
+
+unsafe extern "C" fn call_conflict(
+    data: *mut c_void,
+    kind: c_int,
+    iter: *mut ffi::sqlite3_changeset_iter,
+) -> c_int {
+    // SAFETY: `data` is the changeset given to sqlite3changeset_apply.
+    let applying = unsafe { &mut *(data as *mut Applying) };
+    if applying.failed || !applying.on_conflict {
+        return SQLITE_CHANGESET_ABORT;
+    }
+    // The iterator does not point to a change on foreign key conflicts
+    let table = if kind == SQLITE_CHANGESET_FOREIGN_KEY {
+        None
+    } else {
+        unsafe { table_name(iter) }
+    };
+    let action = applying.caller.call(|done| Call::Conflict {
+        id: applying.id,
+        db: applying.db,
+        kind,
+        table,
+        done,
+    });
+    match action.flatten() {
+        Some(action) => action,
+        None => {
+            applying.failed = true;
+            SQLITE_CHANGESET_ABORT
+        }
+    }
+}
+
+unsafe fn table_name(iter: *mut ffi::sqlite3_changeset_iter) -> Option<String> {
+    let mut table: *const c_char = ptr::null();
+    let mut columns: c_int = 0;
+    let mut op: c_int = 0;
+    let mut indirect: c_int = 0;
+    let code =
+        unsafe { ffi::sqlite3changeset_op(iter, &mut table, &mut columns, &mut op, &mut indirect) };
+    if code != SQLITE_OK || table.is_null() {
+        return None;
+    }
+    Some(
+        unsafe { CStr::from_ptr(table) }
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+fn to_cstring(ctx: &Ctx<'_>, name: &str) -> Result<CString> {
+    CString::new(name).map_err(|_| {
+        Exception::throw_type(
+            ctx,
+            "Table and database names must not contain NUL characters",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rquickjs::CatchResultExt;
+    use rquickjs_extra_test::{ModuleEvaluator, call_test, test_async_with};
+
+    use crate::SqliteModule;
+
+    #[tokio::test]
+    async fn test_session_changeset() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        const schema = "CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT); CREATE TABLE other (id INTEGER PRIMARY KEY)";
+
+                        export async function test() {
+                            const source = await open({ inMemory: true });
+                            await source.exec(schema);
+                            await source.exec("INSERT INTO test (id, name) VALUES (1, 'foo'), (2, 'bar')");
+                            const session = await source.session({ tables: ["test"] });
+                            await session.exec("INSERT INTO test (id, name) VALUES (3, 'baz')");
+                            await session.exec("UPDATE test SET name = 'qux' WHERE id = 1");
+                            await session.exec("INSERT INTO other (id) VALUES (1)");
+                            const stmt = await session.prepare("DELETE FROM test WHERE id = ?");
+                            await stmt.run(2);
+                            const changeset = await session.changeset();
+                            const patchset = await session.patchset();
+                            await session.close();
+
+                            const target = await open({ inMemory: true });
+                            await target.exec(schema);
+                            await target.exec("INSERT INTO test (id, name) VALUES (1, 'changed'), (2, 'bar'), (3, 'other')");
+                            const conflicts = [];
+                            const applied = await target.applyChangeset(changeset, {
+                                onConflict: (type, table) => {
+                                    conflicts.push(`${type}:${table}`);
+                                    return "replace";
+                                },
+                            });
+                            const rows = await target.prepare("SELECT name FROM test ORDER BY id").then((s) => s.pluck().all());
+                            const others = await target.prepare("SELECT count(*) FROM other").then((s) => s.pluck().get());
+
+                            const aborted = await target.applyChangeset(patchset);
+                            const skipped = await target.applyChangeset(patchset, { filter: (table) => table !== "test" });
+                            let error;
+                            try {
+                                await session.changeset();
+                            } catch (e) {
+                                error = e.message;
+                            }
+                            await source.close();
+                            await target.close();
+                            return [
+                                changeset instanceof Uint8Array && patchset.length < changeset.length,
+                                applied,
+                                conflicts.join(" "),
+                                rows.join(" "),
+                                others,
+                                aborted,
+                                skipped,
+                                error,
+                            ].join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    "true,true,data:test conflict:test,qux baz,0,false,true,Session is closed"
+                );
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_session_apply_trigger() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const source = await open({ inMemory: true });
+                            await source.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+                            const session = await source.session();
+                            await session.exec("INSERT INTO test (id, name) VALUES (1, 'foo'), (2, 'bar')");
+                            const changeset = await session.changeset();
+                            await session.close();
+
+                            const target = await open({ inMemory: true });
+                            await target.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+                            await target.exec("CREATE TABLE log (name TEXT)");
+                            await target.function("shout", (name) => name.toUpperCase());
+                            await target.exec("CREATE TRIGGER logged AFTER INSERT ON test BEGIN INSERT INTO log (name) VALUES (shout(new.name)); END");
+                            const applied = await target.applyChangeset(changeset, { filter: (table) => table === "test" });
+                            const names = await target.prepare("SELECT name FROM log ORDER BY name").then((s) => s.pluck().all());
+
+                            let error;
+                            try {
+                                await target.applyChangeset(changeset, {
+                                    onConflict: () => {
+                                        throw new Error("conflict");
+                                    },
+                                });
+                            } catch (e) {
+                                error = e.message;
+                            }
+                            await source.close();
+                            await target.close();
+                            return [applied, names.join(" "), error].join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(result, "true,BAR FOO,conflict");
+            })
+        })
+        .await;
+    }
+}
@@ -1,12 +1,22 @@
-use rquickjs::function::Rest;
-use rquickjs::{Ctx, JsLifetime, Object, Result, class::Trace};
-use rquickjs_extra_utils::result::ResultExt;
-use sqlx::Sqlite;
+use std::cell::RefCell;
+
+use rquickjs::atom::PredefinedAtom;
+use rquickjs::function::{Opt, Rest, This};
+use rquickjs::{Class, Ctx, Exception, Function, JsLifetime, Object, Result, class::Trace};
 use sqlx::query::Query;
 use sqlx::sqlite::SqliteArguments;
-use sqlx::{Column as _, Row as _, SqlitePool, Statement as _, sqlite::SqliteStatement};
+use sqlx::{Connection as _, Executor, Sqlite, SqliteConnection};
+use sqlx::{Statement as _, sqlite::SqliteStatement};
 
-use super::{Argument, Value};
+use super::Argument;
+use super::cancel::QueryOptions;
+use super::connection::Connection;
+use super::error::ResultExt as _;
+use super::iterator::RowIterator;
+use super::parameters::Parameters;
+use super::raw::{self, ColumnInfo};
+use super::row::{RowMode, RowReader};
+use super::value::{ReadOptions, rowid_into_js};
 
 #[derive(Trace, JsLifetime)]
 #[rquickjs::class]
@@ -14,12 +24,56 @@ pub struct Statement {
     #[qjs(skip_trace)]
     stmt: SqliteStatement<'static>,
     #[qjs(skip_trace)]
-    pool: SqlitePool,
+    parameters: Parameters,
+    #[qjs(skip_trace)]
+    connection: Connection,
+    #[qjs(skip_trace)]
+    reader: RefCell<RowReader>,
+    #[qjs(skip_trace)]
+    sql: String,
+    #[qjs(skip_trace)]
+    columns: Vec<ColumnInfo>,
 }
 
 impl Statement {
-    pub fn new(stmt: SqliteStatement<'static>, pool: SqlitePool) -> Self {
-        Self { stmt, pool }
+    /// Prepares `sql` on `connection`, reading its parameters and the metadata of its columns.
+    pub(crate) async fn prepare(
+        ctx: &Ctx<'_>,
+        connection: Connection,
+        sql: &str,
+        options: ReadOptions,
+    ) -> Result<Self> {
+        let mut conn = connection.acquire(ctx).await?;
+        let (parameters, columns) = raw::describe(&mut conn, sql).await.or_throw_sql(ctx, sql)?;
+        let parameters = Parameters::new(parameters);
+        let stmt = sqlx::Statement::to_owned(
+            &conn
+                .prepare(&parameters.sql(sql))
+                .await
+                .or_throw_sql(ctx, sql)?,
+        );
+        drop(conn);
+        Ok(Self {
+            stmt,
+            parameters,
+            connection,
+            reader: RefCell::new(RowReader::new(&columns, options)),
+            sql: sql.to_owned(),
+            columns,
+        })
+    }
+
+    fn toggle_mode(&self, mode: RowMode, toggle: bool) {
+        let mut reader = self.reader.borrow_mut();
+        if toggle {
+            reader.set_mode(mode);
+        } else if reader.mode() == mode {
+            reader.set_mode(RowMode::Object);
+        }
+    }
+
+    fn reader(&self) -> RowReader {
+        self.reader.borrow().clone()
     }
 
     fn query<'js, 'q>(
@@ -36,15 +90,62 @@ impl Statement {
         }
         Ok(query)
     }
+}
 
-    fn row_to_object<'js>(ctx: &Ctx<'js>, row: &sqlx::sqlite::SqliteRow) -> Result<Object<'js>> {
-        let obj = Object::new(ctx.clone())?;
-        for column in row.columns() {
-            let value = Value::try_read(ctx, column, row)?;
-            obj.set(column.name(), value)?;
+/// Executes `queries` in a single transaction, returning the total number
+/// of changes and the last inserted rowid.
+async fn execute_many<'q>(
+    conn: &mut SqliteConnection,
+    queries: Vec<Query<'q, Sqlite, SqliteArguments<'q>>>,
+) -> std::result::Result<(u64, i64), sqlx::Error> {
+    if queries.is_empty() {
+        return Ok((0, 0));
+    }
+    // Nested in a savepoint when the connection is in a transaction, dropping
+    // it on error rolls back
+    let mut tx = conn.begin().await?;
+    let mut changes = 0;
+    let mut last_insert_rowid = 0;
+    for query in queries {
+        let res = query.execute(&mut *tx).await?;
+        changes += res.rows_affected();
+        last_insert_rowid = res.last_insert_rowid();
+    }
+    tx.commit().await?;
+    Ok((changes, last_insert_rowid))
+}
+
+/// Collects the parameters of each execution from an iterable, items that
+/// are not arrays are a single parameter.
+fn collect_rows<'js>(
+    ctx: &Ctx<'js>,
+    rows: rquickjs::Value<'js>,
+) -> Result<Vec<Vec<rquickjs::Value<'js>>>> {
+    let iterator = rows
+        .as_object()
+        .map(|obj| obj.get::<_, rquickjs::Value<'js>>(PredefinedAtom::SymbolIterator))
+        .transpose()?
+        .and_then(|value| value.into_function())
+        .ok_or_else(|| Exception::throw_type(ctx, "Rows must be an iterable of parameters"))?;
+    let iterator: Object<'js> = iterator.call((This(rows),))?;
+    let next: Function<'js> = iterator.get(PredefinedAtom::Next)?;
+
+    let mut params = Vec::new();
+    loop {
+        let result: Object<'js> = next.call((This(iterator.clone()),))?;
+        if result
+            .get::<_, Option<bool>>(PredefinedAtom::Done)?
+            .unwrap_or(false)
+        {
+            break;
         }
-        Ok(obj)
+        let item: rquickjs::Value<'js> = result.get(PredefinedAtom::Value)?;
+        params.push(match item.as_array() {
+            Some(array) => array.iter().collect::<Result<_>>()?,
+            None => vec![item],
+        });
     }
+    Ok(params)
 }
 
 #[rquickjs::methods(rename_all = "camelCase")]
@@ -52,16 +153,22 @@ impl Statement {
     async fn all<'js>(
         &self,
         ctx: Ctx<'js>,
-        anon_params: Rest<Argument<'js>>,
-    ) -> Result<Vec<Object<'js>>> {
-        let query = self.query(&ctx, &anon_params.0)?;
+        params: Rest<rquickjs::Value<'js>>,
+    ) -> Result<Vec<rquickjs::Value<'js>>> {
+        let reader = self.reader();
+        let mut params = params.0;
+        let options = QueryOptions::split(&ctx, &self.parameters, &mut params)?;
+        let arguments = self.parameters.arguments(&ctx, params)?;
+        let query = self.query(&ctx, &arguments)?;
+        let mut conn = self.connection.acquire(&ctx).await?;
 
-        let rows = query.fetch_all(&self.pool).await.or_throw(&ctx)?;
+        let abort = options.start(&ctx, &mut conn).await?;
+        let rows = query.fetch_all(&mut *conn).await;
+        let rows = abort.finish(&ctx, &mut conn, rows, &self.sql).await?;
 
         let mut res = Vec::with_capacity(rows.len());
         for row in rows {
-            let obj = Self::row_to_object(&ctx, &row)?;
-            res.push(obj);
+            res.push(reader.read(&ctx, &row)?);
         }
         Ok(res)
     }
@@ -69,32 +176,166 @@ impl Statement {
     async fn get<'js>(
         &self,
         ctx: Ctx<'js>,
-        anon_params: Rest<Argument<'js>>,
-    ) -> Result<Option<Object<'js>>> {
-        let query = self.query(&ctx, &anon_params.0)?;
+        params: Rest<rquickjs::Value<'js>>,
+    ) -> Result<Option<rquickjs::Value<'js>>> {
+        let reader = self.reader();
+        let mut params = params.0;
+        let options = QueryOptions::split(&ctx, &self.parameters, &mut params)?;
+        let arguments = self.parameters.arguments(&ctx, params)?;
+        let query = self.query(&ctx, &arguments)?;
+        let mut conn = self.connection.acquire(&ctx).await?;
 
-        let Some(row) = query.fetch_optional(&self.pool).await.or_throw(&ctx)? else {
+        let abort = options.start(&ctx, &mut conn).await?;
+        let row = query.fetch_optional(&mut *conn).await;
+        let Some(row) = abort.finish(&ctx, &mut conn, row, &self.sql).await? else {
             return Ok(None);
         };
 
-        let obj = Self::row_to_object(&ctx, &row)?;
-        Ok(Some(obj))
+        Ok(Some(reader.read(&ctx, &row)?))
+    }
+
+    fn iterate<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        params: Rest<rquickjs::Value<'js>>,
+    ) -> Result<RowIterator> {
+        let arguments = self.parameters.arguments(&ctx, params.0)?;
+        Ok(RowIterator::spawn(
+            &ctx,
+            self.stmt.clone(),
+            arguments,
+            self.connection.clone(),
+            self.reader(),
+        ))
     }
 
     async fn run<'js>(
         &self,
         ctx: Ctx<'js>,
-        anon_params: Rest<Argument<'js>>,
+        params: Rest<rquickjs::Value<'js>>,
     ) -> Result<Object<'js>> {
-        let query = self.query(&ctx, &anon_params.0)?;
+        let reader = self.reader();
+        let mut params = params.0;
+        let options = QueryOptions::split(&ctx, &self.parameters, &mut params)?;
+        let arguments = self.parameters.arguments(&ctx, params)?;
+        let query = self.query(&ctx, &arguments)?;
+        let mut conn = self.connection.acquire(&ctx).await?;
 
-        let res = query.execute(&self.pool).await.or_throw(&ctx)?;
+        let abort = options.start(&ctx, &mut conn).await?;
+        let res = query.execute(&mut *conn).await;
+        let res = abort.finish(&ctx, &mut conn, res, &self.sql).await?;
 
         let obj = Object::new(ctx.clone())?;
         obj.set("changes", res.rows_affected())?;
-        obj.set("lastInsertRowid", res.last_insert_rowid())?;
+        obj.set(
+            "lastInsertRowid",
+            rowid_into_js(
+                &ctx,
+                res.last_insert_rowid(),
+                reader.options().safe_integers,
+            )?,
+        )?;
+        Ok(obj)
+    }
+
+    /// Runs the statement once for each item of `rows` in a single
+    /// transaction on one connection, nested in a savepoint when the
+    /// statement belongs to a transaction.
+    async fn run_many<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        rows: rquickjs::Value<'js>,
+        options: Opt<rquickjs::Value<'js>>,
+    ) -> Result<Object<'js>> {
+        let reader = self.reader();
+        let options = QueryOptions::from_js(&ctx, options.0)?;
+        let arguments = collect_rows(&ctx, rows)?
+            .into_iter()
+            .map(|params| self.parameters.arguments(&ctx, params))
+            .collect::<Result<Vec<_>>>()?;
+        let queries = arguments
+            .iter()
+            .map(|arguments| self.query(&ctx, arguments))
+            .collect::<Result<Vec<_>>>()?;
+        let mut conn = self.connection.acquire(&ctx).await?;
+
+        let abort = options.start(&ctx, &mut conn).await?;
+        let res = execute_many(&mut conn, queries).await;
+        let (changes, last_insert_rowid) = abort.finish(&ctx, &mut conn, res, &self.sql).await?;
+
+        let obj = Object::new(ctx.clone())?;
+        obj.set("changes", changes)?;
+        obj.set(
+            "lastInsertRowid",
+            rowid_into_js(&ctx, last_insert_rowid, reader.options().safe_integers)?,
+        )?;
         Ok(obj)
     }
+
+    fn columns<'js>(&self, ctx: Ctx<'js>) -> Result<Vec<Object<'js>>> {
+        self.columns
+            .iter()
+            .map(|column| {
+                let obj = Object::new(ctx.clone())?;
+                obj.set("name", column.name.as_str())?;
+                obj.set("column", column.origin.as_deref())?;
+                obj.set("table", column.table.as_deref())?;
+                obj.set("database", column.database.as_deref())?;
+                obj.set("type", column.declared_type.as_deref())?;
+                Ok(obj)
+            })
+            .collect()
+    }
+
+    #[qjs(get)]
+    fn param_count(&self) -> usize {
+        self.parameters.count()
+    }
+
+    #[qjs(get)]
+    fn sql(&self) -> String {
+        self.sql.clone()
+    }
+
+    async fn expanded_sql<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        params: Rest<rquickjs::Value<'js>>,
+    ) -> Result<Option<String>> {
+        let arguments = self.parameters.arguments(&ctx, params.0)?;
+        let mut conn = self.connection.acquire(&ctx).await?;
+        raw::expanded_sql(&mut conn, self.stmt.sql(), &arguments)
+            .await
+            .or_throw_sql(&ctx, &self.sql)
+    }
+
+    /// Toggles reading integers as `BigInt` for this statement.
+    fn safe_integers<'js>(this: This<Class<'js, Self>>, toggle: Opt<bool>) -> Class<'js, Self> {
+        {
+            let stmt = this.0.borrow();
+            let mut reader = stmt.reader.borrow_mut();
+            let mut options = reader.options();
+            options.safe_integers = toggle.0.unwrap_or(true);
+            reader.set_options(options);
+        }
+        this.0
+    }
+
+    /// Toggles returning rows as arrays of values in column order.
+    fn raw<'js>(this: This<Class<'js, Self>>, toggle: Opt<bool>) -> Class<'js, Self> {
+        this.0
+            .borrow()
+            .toggle_mode(RowMode::Array, toggle.0.unwrap_or(true));
+        this.0
+    }
+
+    /// Toggles returning only the value of the first column of rows.
+    fn pluck<'js>(this: This<Class<'js, Self>>, toggle: Opt<bool>) -> Class<'js, Self> {
+        this.0
+            .borrow()
+            .toggle_mode(RowMode::Pluck, toggle.0.unwrap_or(true));
+        this.0
+    }
 }
 
 #[cfg(test)]
@@ -208,4 +449,473 @@ mod tests {
         })
         .await;
     }
+
+    #[tokio::test]
+    async fn test_statement_named_parameters() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)");
+                            const insert = await db.prepare("INSERT INTO test (id, name, age) VALUES (?1, $name, @age)");
+                            await insert.run({ "?1": 3, $name: "test", age: 42 });
+                            await insert.run({ 1: 4, name: "test2", "@age": 7 });
+
+                            const select = await db.prepare("SELECT * FROM test WHERE name = :name OR id = :name");
+                            const row = await select.get({ name: "test" });
+
+                            const errors = [];
+                            try {
+                                await select.get({ name: "test", unknown: 1 });
+                            } catch (e) {
+                                errors.push(`${e.name}: ${e.message}`);
+                            }
+                            try {
+                                await select.get({});
+                            } catch (e) {
+                                errors.push(`${e.name}: ${e.message}`);
+                            }
+                            const count = await (await db.prepare("SELECT COUNT(*) AS count FROM test")).get();
+
+                            // Objects are bound as JSON when there are no named parameters
+                            const numbered = await db.prepare("SELECT ?1 AS id, json_extract(?2, '$.a') AS a");
+                            const json = await numbered.get(5, { a: 6 });
+
+                            // Positional values must match the parameters
+                            for (const params of [[1, 2, 3], [1], []]) {
+                                try {
+                                    await numbered.get(...params);
+                                } catch (e) {
+                                    errors.push(`${e.name}: ${e.message}`);
+                                }
+                            }
+                            const mixed = await db.prepare("SELECT :a, ?");
+                            try {
+                                await mixed.get(1, 2, { a: 3 });
+                            } catch (e) {
+                                errors.push(`${e.name}: ${e.message}`);
+                            }
+                            try {
+                                await db.prepare("SELECT ?9999999999");
+                            } catch (e) {
+                                errors.push(e.code);
+                            }
+                            return [row.id, row.age, count.count, json.id, json.a, ...errors].join("|");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    [
+                        "3|42|2|5|6",
+                        "TypeError: Unknown named parameter 'unknown'",
+                        "TypeError: Missing named parameter ':name'",
+                        "RangeError: Too many parameter values were provided, expected 2 but got 3",
+                        "RangeError: Too few parameter values were provided, expected 2 but got 1",
+                        "RangeError: Too few parameter values were provided, expected 2 but got 0",
+                        "RangeError: Too many parameter values were provided, expected 1 but got 2",
+                        "SQLITE_ERROR",
+                    ]
+                    .join("|")
+                );
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_statement_iterate() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true, maxConnections: 1 });
+                            await db.exec(`
+                                CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT);
+                                WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 1000)
+                                INSERT INTO test (name) SELECT 'row' || n FROM seq;
+                            `);
+                            const stmt = await db.prepare("SELECT * FROM test WHERE id > ? ORDER BY id");
+
+                            let sum = 0;
+                            for await (const row of stmt.iterate(0)) {
+                                sum += row.id;
+                            }
+
+                            let last;
+                            for await (const row of stmt.iterate(10)) {
+                                last = row.name;
+                                if (row.id === 20) {
+                                    break;
+                                }
+                            }
+
+                            // The single pooled connection must have been released
+                            const count = await (await db.prepare("SELECT COUNT(*) AS count FROM test")).get();
+                            return `${sum}:${last}:${count.count}`;
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(result, "500500:row20:1000");
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_statement_declared_types() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true, declaredTypes: true });
+                            await db.exec("CREATE TABLE test (active BOOLEAN, created DATETIME, data JSON)");
+                            const insert = await db.prepare("INSERT INTO test VALUES (?, ?, ?)");
+                            await insert.run(true, new Date(Date.UTC(2024, 0, 2, 3, 4, 5)), { tags: ["a"] });
+                            await db.exec("INSERT INTO test VALUES (0, '2024-01-02 03:04:05', '[1, 2]')");
+                            const rows = await db.prepare("SELECT * FROM test").then((s) => s.all());
+                            return rows.map((row) => [
+                                typeof row.active,
+                                row.active,
+                                row.created instanceof Date,
+                                row.created.toISOString(),
+                                JSON.stringify(row.data),
+                            ].join(" ")).join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    "boolean true true 2024-01-02T03:04:05.000Z {\"tags\":[\"a\"]},boolean false true 2024-01-02T03:04:05.000Z [1,2]"
+                );
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_statement_safe_integers() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, small INTEGER)");
+                            await db.exec("INSERT INTO test VALUES (9007199254740993, 1)");
+                            const stmt = await db.prepare("SELECT * FROM test");
+                            let error;
+                            try {
+                                await stmt.get();
+                            } catch (e) {
+                                error = e instanceof RangeError;
+                            }
+                            const row = await stmt.safeIntegers().get();
+                            const off = await stmt.safeIntegers(false).get().catch((e) => e.name);
+                            db.safeIntegers(true);
+                            const other = await db.prepare("SELECT small FROM test").then((s) => s.get());
+
+                            // Rowids are never lost after an insert, even without safe integers
+                            db.safeIntegers(false);
+                            const insert = await db.prepare("INSERT INTO test (id, small) VALUES (?, 2)");
+                            const { lastInsertRowid } = await insert.run(9007199254740995n);
+                            return [error, typeof row.id, row.id, typeof row.small, off, typeof other.small, lastInsertRowid].join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    "true,bigint,9007199254740993,bigint,RangeError,bigint,9007199254740995"
+                );
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_statement_metadata() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+                            const sql = "SELECT id AS key, name, 1 AS one FROM test WHERE id > :min AND name = :name";
+                            const stmt = await db.prepare(sql);
+                            const columns = stmt.columns().map((c) => [c.name, c.column, c.table, c.database, c.type].join("|"));
+                            const expanded = await stmt.expandedSql({ min: 1, name: "it's" });
+                            return [stmt.sql === sql, stmt.paramCount, columns.join(";"), expanded].join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    "true,2,key|id|test|main|INTEGER;name|name|test|main|TEXT;one||||,SELECT id AS key, name, 1 AS one FROM test WHERE id > 1 AND name = 'it''s'"
+                );
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_statement_raw_and_pluck() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE a (id INTEGER PRIMARY KEY, name TEXT)");
+                            await db.exec("CREATE TABLE b (id INTEGER PRIMARY KEY, name TEXT)");
+                            await db.exec("INSERT INTO a VALUES (1, 'foo'), (2, 'bar')");
+                            await db.exec("INSERT INTO b VALUES (1, 'baz'), (2, 'qux')");
+                            const stmt = await db.prepare("SELECT * FROM a JOIN b ON a.id = b.id ORDER BY a.id");
+                            const raw = await stmt.raw().all();
+                            const plucked = await stmt.pluck().all();
+                            const first = await stmt.pluck(false).get();
+                            const iterated = [];
+                            for await (const row of stmt.raw().iterate()) {
+                                iterated.push(row.length);
+                            }
+                            return [JSON.stringify(raw), plucked.join(" "), JSON.stringify(first), iterated.join(" ")].join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    r#"[[1,"foo",1,"baz"],[2,"bar",2,"qux"]],1 2,{"id":1,"name":"baz"},4 4"#
+                );
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_statement_abort() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        class Signal {
+                            aborted = false;
+                            reason = undefined;
+                            listeners = [];
+                            addEventListener(type, listener) {
+                                this.listeners.push(listener);
+                            }
+                            removeEventListener(type, listener) {
+                                this.listeners = this.listeners.filter((l) => l !== listener);
+                            }
+                            abort(reason) {
+                                this.aborted = true;
+                                this.reason = reason;
+                                this.listeners.forEach((listener) => listener());
+                            }
+                        }
+
+                        const fails = async (f) => {
+                            try {
+                                await f();
+                                return "ok";
+                            } catch (e) {
+                                return [e.name, e.code, e.cause ?? ""].join(":");
+                            }
+                        };
+
+                        const forever = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            const stmt = await db.prepare(forever);
+                            const results = [];
+                            results.push(await fails(() => stmt.get({ timeout: 20 })));
+
+                            const signal = new Signal();
+                            const running = fails(() => stmt.all({ signal }));
+                            signal.abort("stop");
+                            results.push(await running, signal.listeners.length);
+
+                            results.push(await fails(() => stmt.run({ signal })));
+                            results.push(await fails(() => db.exec(forever, { timeout: 0 })));
+                            results.push(await fails(() => db.transaction((tx) => tx.exec(forever, { timeout: 0 }))));
+
+                            // Connections can be used again once interrupted, and objects are
+                            // only options once every parameter is bound
+                            const one = await db.prepare("SELECT :timeout AS timeout");
+                            results.push(JSON.stringify(await one.get({ timeout: 1 })));
+                            const json = await db.prepare("SELECT json(?) AS json");
+                            results.push((await json.get({ timeout: 1 })).json);
+                            results.push((await json.get(2, { timeout: 1000 })).json);
+                            results.push(await fails(() => stmt.get({ timeout: -1 })));
+                            return results.join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(
+                    result,
+                    r#"AbortError:ABORT_ERR:,AbortError:ABORT_ERR:stop,0,AbortError:ABORT_ERR:stop,AbortError:ABORT_ERR:,AbortError:ABORT_ERR:,{"timeout":1},{"timeout":1},2,TypeError::"#
+                );
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_statement_run_many() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT UNIQUE)");
+                            const insert = await db.prepare("INSERT INTO test (name) VALUES (?)");
+                            const first = await insert.runMany([["a"], ["b"], "c"]);
+                            const named = await db.prepare("INSERT INTO test (name) VALUES ($name)");
+                            function* names() {
+                                yield { name: "d" };
+                                yield { name: "e" };
+                            }
+                            const second = await named.runMany(names());
+                            let error;
+                            try {
+                                await insert.runMany([["f"], ["a"]]);
+                            } catch (e) {
+                                error = e.code;
+                            }
+                            await db.transaction(async (tx) => {
+                                const stmt = await tx.prepare("INSERT INTO test (name) VALUES (?)");
+                                await stmt.runMany([["g"]]);
+                                try {
+                                    await stmt.runMany([["h"], ["g"]]);
+                                } catch {}
+                            });
+                            const empty = await insert.runMany([]);
+                            const rows = await (await db.prepare("SELECT name FROM test")).pluck().all();
+                            return [
+                                first.changes,
+                                first.lastInsertRowid,
+                                second.changes,
+                                second.lastInsertRowid,
+                                error,
+                                empty.changes,
+                                rows.join(""),
+                            ].join();
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(result, "3,3,2,5,SQLITE_CONSTRAINT_UNIQUE,0,abcdeg");
+            })
+        })
+        .await;
+    }
 }
@@ -0,0 +1,358 @@
+use std::cell::Cell;
+use std::ffi::{CStr, c_int};
+use std::ptr::NonNull;
+use std::rc::Rc;
+
+use libsqlite3_sys::{
+    SQLITE_DONE, SQLITE_OK, SQLITE_ROW, sqlite3, sqlite3_changes64, sqlite3_clear_bindings,
+    sqlite3_column_count, sqlite3_column_name, sqlite3_last_insert_rowid, sqlite3_reset,
+    sqlite3_step,
+};
+use rquickjs::{
+    Class, Ctx, Exception, JsLifetime, Object, Result, Value,
+    atom::PredefinedAtom,
+    class::Trace,
+    function::{Opt, Rest, This},
+};
+
+use super::database_sync::SyncConnection;
+use super::error::SqliteError;
+use super::parameters::{Parameters, is_plain_object};
+use super::raw::RawStatement;
+use super::value::{self, DeclaredType, ReadOptions, integer_into_js, rowid_into_js};
+
+/// A prepared statement shared by a `StatementSync` and its iterators.
+struct Prepared {
+    connection: Rc<SyncConnection>,
+    stmt: RawStatement,
+    parameters: Parameters,
+    sql: String,
+}
+
+impl Prepared {
+    /// Resets the statement and binds `params` to it, returning the connection.
+    fn bind<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        mut params: Vec<Value<'js>>,
+        allow_bare_names: bool,
+    ) -> Result<NonNull<sqlite3>> {
+        let db = self.connection.handle(ctx)?;
+        // node:sqlite takes named parameters before the anonymous ones
+        if params.len() > 1 && is_plain_object(ctx, &params[0])? {
+            let named = params.remove(0);
+            params.push(named);
+        }
+        let arguments = self
+            .parameters
+            .arguments_with(ctx, params, allow_bare_names)?;
+
+        let stmt = self.stmt.as_ptr();
+        // SAFETY: The statement and its connection are only used by this context.
+        unsafe {
+            sqlite3_reset(stmt);
+            sqlite3_clear_bindings(stmt);
+            for (index, argument) in arguments.iter().enumerate() {
+                if argument.bind_raw(stmt, index as c_int + 1) != SQLITE_OK {
+                    return Err(self.error(ctx, db));
+                }
+            }
+        }
+        Ok(db)
+    }
+
+    /// Steps the statement, returns `true` if it is positioned on a row.
+    fn step(&self, ctx: &Ctx<'_>, db: NonNull<sqlite3>) -> Result<bool> {
+        // SAFETY: The statement and its connection are only used by this context.
+        match unsafe { sqlite3_step(self.stmt.as_ptr()) } {
+            SQLITE_ROW => Ok(true),
+            SQLITE_DONE => {
+                self.reset();
+                Ok(false)
+            }
+            _ => Err(self.error(ctx, db)),
+        }
+    }
+
+    fn reset(&self) {
+        // SAFETY: The statement is only used by this context.
+        unsafe { sqlite3_reset(self.stmt.as_ptr()) };
+    }
+
+    /// Throws the last error of the connection, then resets the statement.
+    fn error(&self, ctx: &Ctx<'_>, db: NonNull<sqlite3>) -> rquickjs::Error {
+        let error = SqliteError::from_handle(db).with_sql(self.sql.as_str());
+        self.reset();
+        error.throw(ctx)
+    }
+
+    /// Reads the current row as an object keyed by column name.
+    fn read<'js>(&self, ctx: &Ctx<'js>, options: ReadOptions) -> Result<Value<'js>> {
+        let stmt = self.stmt.as_ptr();
+        let row = Object::new(ctx.clone())?;
+        // SAFETY: The statement is positioned on a row until it is stepped again.
+        unsafe {
+            for index in 0..sqlite3_column_count(stmt) {
+                let name = sqlite3_column_name(stmt, index);
+                let name = if name.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(name).to_string_lossy().into_owned()
+                };
+                let value = value::Value::try_from_column(ctx, stmt, index)?;
+                row.set(
+                    name,
+                    value.into_js_typed(ctx, DeclaredType::Other, options.safe_integers)?,
+                )?;
+            }
+        }
+        Ok(row.into_value())
+    }
+}
+
+/// A prepared statement of a `DatabaseSync`, compatible with `node:sqlite`.
+#[derive(Trace, JsLifetime)]
+#[rquickjs::class]
+pub struct StatementSync {
+    #[qjs(skip_trace)]
+    prepared: Rc<Prepared>,
+    #[qjs(skip_trace)]
+    options: Cell<ReadOptions>,
+    #[qjs(skip_trace)]
+    allow_bare_named_parameters: Cell<bool>,
+}
+
+impl StatementSync {
+    pub fn prepare(ctx: &Ctx<'_>, connection: Rc<SyncConnection>, sql: String) -> Result<Self> {
+        let db = connection.handle(ctx)?;
+        if sql.contains('\0') {
+            return Err(Exception::throw_type(
+                ctx,
+                "SQL must not contain NUL characters",
+            ));
+        }
+        // SAFETY: The connection is only used by this context and outlives
+        // the statement, as it is only closed once its statements are finalized.
+        let stmt = unsafe { RawStatement::prepare(db, &sql) }
+            .map_err(|_| {
+                SqliteError::from_handle(db)
+                    .with_sql(sql.as_str())
+                    .throw(ctx)
+            })?
+            .ok_or_else(|| Exception::throw_message(ctx, "SQL does not contain a statement"))?;
+        let parameters = Parameters::new(stmt.parameters());
+        Ok(Self {
+            prepared: Rc::new(Prepared {
+                connection,
+                stmt,
+                parameters,
+                sql,
+            }),
+            options: Cell::default(),
+            allow_bare_named_parameters: Cell::new(true),
+        })
+    }
+
+    fn bind<'js>(&self, ctx: &Ctx<'js>, params: Vec<Value<'js>>) -> Result<NonNull<sqlite3>> {
+        self.prepared
+            .bind(ctx, params, self.allow_bare_named_parameters.get())
+    }
+}
+
+#[rquickjs::methods(rename_all = "camelCase")]
+impl StatementSync {
+    /// Only exposed so `instanceof` works, statements are created by `prepare`.
+    #[qjs(constructor)]
+    fn construct(ctx: Ctx<'_>) -> Result<Self> {
+        Err(Exception::throw_type(&ctx, "Illegal constructor"))
+    }
+
+    /// The SQL used to prepare the statement.
+    #[qjs(get, rename = "sourceSQL")]
+    fn source_sql(&self) -> String {
+        self.prepared.sql.clone()
+    }
+
+    /// The SQL of the statement with the parameters of its last execution expanded.
+    #[qjs(get, rename = "expandedSQL")]
+    fn expanded_sql(&self, ctx: Ctx<'_>) -> Result<Option<String>> {
+        self.prepared.connection.handle(&ctx)?;
+        Ok(self.prepared.stmt.expanded_sql())
+    }
+
+    /// Toggles reading integers as `BigInt`.
+    fn set_read_big_ints(&self, enabled: bool) {
+        let mut options = self.options.get();
+        options.safe_integers = enabled;
+        self.options.set(options);
+    }
+
+    /// Toggles binding named parameters by name without their prefix.
+    fn set_allow_bare_named_parameters(&self, enabled: bool) {
+        self.allow_bare_named_parameters.set(enabled);
+    }
+
+    fn all<'js>(&self, ctx: Ctx<'js>, params: Rest<Value<'js>>) -> Result<Vec<Value<'js>>> {
+        let prepared = &self.prepared;
+        let db = self.bind(&ctx, params.0)?;
+        let mut rows = Vec::new();
+        while prepared.step(&ctx, db)? {
+            rows.push(prepared.read(&ctx, self.options.get())?);
+        }
+        Ok(rows)
+    }
+
+    fn get<'js>(&self, ctx: Ctx<'js>, params: Rest<Value<'js>>) -> Result<Option<Value<'js>>> {
+        let prepared = &self.prepared;
+        let db = self.bind(&ctx, params.0)?;
+        if !prepared.step(&ctx, db)? {
+            return Ok(None);
+        }
+        let row = prepared.read(&ctx, self.options.get());
+        prepared.reset();
+        row.map(Some)
+    }
+
+    fn run<'js>(&self, ctx: Ctx<'js>, params: Rest<Value<'js>>) -> Result<Object<'js>> {
+        let prepared = &self.prepared;
+        let db = self.bind(&ctx, params.0)?;
+        while prepared.step(&ctx, db)? {}
+
+        let result = Object::new(ctx.clone())?;
+        // SAFETY: The connection is only used by this context.
+        let (changes, rowid) = unsafe {
+            (
+                sqlite3_changes64(db.as_ptr()),
+                sqlite3_last_insert_rowid(db.as_ptr()),
+            )
+        };
+        let safe_integers = self.options.get().safe_integers;
+        result.set("changes", integer_into_js(&ctx, changes, safe_integers)?)?;
+        result.set(
+            "lastInsertRowid",
+            rowid_into_js(&ctx, rowid, safe_integers)?,
+        )?;
+        Ok(result)
+    }
+
+    /// Returns an iterator stepping the statement as rows are consumed.
+    fn iterate<'js>(
+        &self,
+        ctx: Ctx<'js>,
+        params: Rest<Value<'js>>,
+    ) -> Result<StatementSyncIterator> {
+        self.bind(&ctx, params.0)?;
+        Ok(StatementSyncIterator {
+            prepared: self.prepared.clone(),
+            options: self.options.get(),
+            done: Cell::new(false),
+        })
+    }
+}
+
+/// Iterator over the rows of a `StatementSync`.
+#[derive(Trace, JsLifetime)]
+#[rquickjs::class]
+pub struct StatementSyncIterator {
+    #[qjs(skip_trace)]
+    prepared: Rc<Prepared>,
+    #[qjs(skip_trace)]
+    options: ReadOptions,
+    #[qjs(skip_trace)]
+    done: Cell<bool>,
+}
+
+impl StatementSyncIterator {
+    fn result<'js>(ctx: &Ctx<'js>, value: Value<'js>, done: bool) -> Result<Object<'js>> {
+        let result = Object::new(ctx.clone())?;
+        result.set(PredefinedAtom::Value, value)?;
+        result.set(PredefinedAtom::Done, done)?;
+        Ok(result)
+    }
+}
+
+#[rquickjs::methods(rename_all = "camelCase")]
+impl StatementSyncIterator {
+    fn next<'js>(&self, ctx: Ctx<'js>) -> Result<Object<'js>> {
+        let prepared = &self.prepared;
+        if !self.done.get() {
+            let db = prepared.connection.handle(&ctx)?;
+            match prepared.step(&ctx, db) {
+                Ok(true) => {
+                    let row = prepared.read(&ctx, self.options)?;
+                    return Self::result(&ctx, row, false);
+                }
+                Ok(false) => self.done.set(true),
+                Err(err) => {
+                    self.done.set(true);
+                    return Err(err);
+                }
+            }
+        }
+        Self::result(&ctx, Value::new_undefined(ctx.clone()), true)
+    }
+
+    #[qjs(rename = "return")]
+    fn finish<'js>(&self, ctx: Ctx<'js>, value: Opt<Value<'js>>) -> Result<Object<'js>> {
+        if !self.done.replace(true) {
+            self.prepared.reset();
+        }
+        let value = value.0.unwrap_or_else(|| Value::new_undefined(ctx.clone()));
+        Self::result(&ctx, value, true)
+    }
+
+    #[qjs(rename = PredefinedAtom::SymbolIterator)]
+    fn iterator<'js>(this: This<Class<'js, Self>>) -> Class<'js, Self> {
+        this.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rquickjs::{CatchResultExt, Class};
+    use rquickjs_extra_test::test_with;
+
+    use crate::{DatabaseSync, StatementSync};
+
+    #[test]
+    fn test_statement_sync_options() {
+        test_with(|ctx| {
+            Class::<DatabaseSync>::define(&ctx.globals()).unwrap();
+            Class::<StatementSync>::define(&ctx.globals()).unwrap();
+            let result = ctx
+                .eval::<String, _>(
+                    r#"
+                const db = new DatabaseSync(":memory:");
+                const stmt = db.prepare("SELECT $a AS a, :b AS b");
+                const bare = stmt.get({ a: 1, b: 2 });
+                stmt.setAllowBareNamedParameters(false);
+                let error;
+                try {
+                    stmt.get({ a: 1, $b: 2 });
+                } catch (e) {
+                    error = e.message;
+                }
+                const prefixed = stmt.get({ $a: 3, ":b": "x" });
+                stmt.setReadBigInts(true);
+                const big = stmt.get({ $a: 9007199254740993n, ":b": null });
+                [
+                    bare.a + bare.b,
+                    error,
+                    prefixed.a + prefixed.b,
+                    typeof big.a,
+                    big.a,
+                    stmt.sourceSQL,
+                    stmt.expandedSQL,
+                    db.location(),
+                ].join()
+            "#,
+                )
+                .catch(&ctx)
+                .unwrap();
+            assert_eq!(
+                result,
+                "3,Unknown named parameter 'a',3x,bigint,9007199254740993,SELECT $a AS a, :b AS b,SELECT 9007199254740993 AS a, NULL AS b,"
+            );
+        })
+    }
+}
@@ -0,0 +1,462 @@
+use std::sync::Arc;
+
+use rquickjs::{
+    CaughtError, Class, Ctx, Exception, Function, JsLifetime, Result, Value, class::Trace,
+    function::Opt, promise::MaybePromise,
+};
+use rquickjs_extra_utils::result::ResultExt;
+use sqlx::{Executor, Sqlite, SqlitePool};
+use tokio::sync::Mutex;
+
+use super::Statement;
+use super::cancel::QueryOptions;
+use super::connection::{Connection, Iterating};
+use super::error::ResultExt as _;
+use super::value::ReadOptions;
+
+/// State shared by a transaction and all of its nested savepoints.
+pub struct TransactionState {
+    tx: Option<sqlx::Transaction<'static, Sqlite>>,
+    // Ids of the open levels, the outermost transaction first
+    levels: Vec<u64>,
+    next_id: u64,
+}
+
+impl TransactionState {
+    pub fn connection(&mut self, id: u64) -> Option<&mut sqlx::Transaction<'static, Sqlite>> {
+        if self.levels.contains(&id) {
+            self.tx.as_mut()
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Trace, JsLifetime)]
+#[rquickjs::class]
+pub struct Transaction {
+    #[qjs(skip_trace)]
+    state: Arc<Mutex<TransactionState>>,
+    #[qjs(skip_trace)]
+    id: u64,
+    #[qjs(skip_trace)]
+    iterating: Iterating,
+    #[qjs(skip_trace)]
+    options: ReadOptions,
+}
+
+impl Transaction {
+    pub async fn start(ctx: &Ctx<'_>, pool: &SqlitePool, options: ReadOptions) -> Result<Self> {
+        let tx = pool.begin().await.or_throw_sqlite(ctx)?;
+        Ok(Self::new(tx, options))
+    }
+
+    /// Starts a transaction with a custom statement, like `BEGIN IMMEDIATE`.
+    pub async fn start_with(
+        ctx: &Ctx<'_>,
+        pool: &SqlitePool,
+        options: ReadOptions,
+        statement: &'static str,
+    ) -> Result<Self> {
+        let tx = pool.begin_with(statement).await.or_throw_sqlite(ctx)?;
+        Ok(Self::new(tx, options))
+    }
+
+    fn new(tx: sqlx::Transaction<'static, Sqlite>, options: ReadOptions) -> Self {
+        let state = TransactionState {
+            tx: Some(tx),
+            levels: vec![0],
+            next_id: 1,
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+            id: 0,
+            iterating: Iterating::default(),
+            options,
+        }
+    }
+
+    pub(crate) fn connection(&self) -> Connection {
+        Connection::Transaction {
+            state: self.state.clone(),
+            id: self.id,
+            iterating: self.iterating.clone(),
+        }
+    }
+
+    async fn savepoint(&self, ctx: &Ctx<'_>) -> Result<Self> {
+        let mut state = self.iterating.lock(ctx, &self.state).await?;
+        if state.levels.last() != Some(&self.id) {
+            return Err(Exception::throw_message(
+                ctx,
+                "Only the innermost transaction can begin a nested transaction",
+            ));
+        }
+        let depth = state.levels.len();
+        let tx = state.tx.as_mut().or_throw(ctx)?;
+        tx.execute(&*format!("SAVEPOINT _sqlite_savepoint_{depth}"))
+            .await
+            .or_throw_sqlite(ctx)?;
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.levels.push(id);
+        Ok(Self {
+            state: self.state.clone(),
+            id,
+            iterating: self.iterating.clone(),
+            options: self.options,
+        })
+    }
+
+    /// Commits or rolls back this level and every level nested in it.
+    /// Returns `false` if the transaction was already finished.
+    pub(crate) async fn finish(&self, ctx: &Ctx<'_>, commit: bool) -> Result<bool> {
+        let mut state = self.iterating.lock(ctx, &self.state).await?;
+        let Some(depth) = state.levels.iter().position(|id| *id == self.id) else {
+            return Ok(false);
+        };
+
+        if depth == 0 {
+            state.levels.clear();
+            let tx = state.tx.take().or_throw(ctx)?;
+            if commit {
+                tx.commit().await.or_throw_sqlite(ctx)?;
+            } else {
+                tx.rollback().await.or_throw_sqlite(ctx)?;
+            }
+        } else {
+            let sql = if commit {
+                format!("RELEASE SAVEPOINT _sqlite_savepoint_{depth}")
+            } else {
+                format!(
+                    "ROLLBACK TO SAVEPOINT _sqlite_savepoint_{depth}; RELEASE SAVEPOINT _sqlite_savepoint_{depth}"
+                )
+            };
+            let tx = state.tx.as_mut().or_throw(ctx)?;
+            sqlx::raw_sql(&sql)
+                .execute(&mut **tx)
+                .await
+                .or_throw_sql(ctx, &sql)?;
+            state.levels.truncate(depth);
+        }
+        Ok(true)
+    }
+
+    /// Runs `callback` with the transaction, committing it if the callback
+    /// succeeds and rolling it back if it throws.
+    pub async fn run<'js>(self, ctx: Ctx<'js>, callback: Function<'js>) -> Result<Value<'js>> {
+        let instance = Class::instance(ctx.clone(), self.clone())?;
+        let result = match callback.call::<_, MaybePromise>((instance,)) {
+            Ok(promise) => promise.into_future::<Value>().await,
+            Err(err) => Err(err),
+        };
+        self.finish_with(&ctx, result).await
+    }
+
+    /// Commits the transaction if `result` is a success, rolls it back if it
+    /// is an error or if committing fails, and returns `result` or the error
+    /// of the commit.
+    pub(crate) async fn finish_with<T>(&self, ctx: &Ctx<'_>, result: Result<T>) -> Result<T> {
+        match result {
+            Ok(value) => {
+                if let Err(err) = self.finish(ctx, true).await {
+                    let err = CaughtError::from_error(ctx, err);
+                    let _ = self.finish(ctx, false).await;
+                    return Err(err.throw(ctx));
+                }
+                Ok(value)
+            }
+            Err(err) => {
+                // Catch the exception before rolling back so it is not lost
+                let err = CaughtError::from_error(ctx, err);
+                let _ = self.finish(ctx, false).await;
+                Err(err.throw(ctx))
+            }
+        }
+    }
+}
+
+#[rquickjs::methods(rename_all = "camelCase")]
+impl Transaction {
+    async fn exec<'js>(&self, ctx: Ctx<'js>, sql: String, options: Opt<Value<'js>>) -> Result<()> {
+        let options = QueryOptions::from_js(&ctx, options.0)?;
+        let mut conn = self.connection().acquire(&ctx).await?;
+        let abort = options.start(&ctx, &mut conn).await?;
+        let result = sqlx::raw_sql(&sql).execute(&mut *conn).await;
+        abort.finish(&ctx, &mut conn, result, &sql).await?;
+        Ok(())
+    }
+
+    async fn prepare(&self, ctx: Ctx<'_>, sql: String) -> Result<Statement> {
+        Statement::prepare(&ctx, self.connection(), &sql, self.options).await
+    }
+
+    async fn begin(&self, ctx: Ctx<'_>) -> Result<Transaction> {
+        self.savepoint(&ctx).await
+    }
+
+    async fn transaction<'js>(&self, ctx: Ctx<'js>, callback: Function<'js>) -> Result<Value<'js>> {
+        let tx = self.savepoint(&ctx).await?;
+        tx.run(ctx, callback).await
+    }
+
+    async fn commit(&self, ctx: Ctx<'_>) -> Result<()> {
+        if !self.finish(&ctx, true).await? {
+            return Err(Exception::throw_message(
+                &ctx,
+                "Transaction is already finished",
+            ));
+        }
+        Ok(())
+    }
+
+    async fn rollback(&self, ctx: Ctx<'_>) -> Result<()> {
+        if !self.finish(&ctx, false).await? {
+            return Err(Exception::throw_message(
+                &ctx,
+                "Transaction is already finished",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rquickjs::CatchResultExt;
+    use rquickjs_extra_test::{ModuleEvaluator, call_test, test_async_with};
+
+    use crate::SqliteModule;
+
+    #[tokio::test]
+    async fn test_transaction_commit() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+                            const result = await db.transaction(async (tx) => {
+                                await tx.exec("INSERT INTO test (name) VALUES ('test')");
+                                const stmt = await tx.prepare("INSERT INTO test (name) VALUES (?)");
+                                await stmt.run('test2');
+                                return "done";
+                            });
+                            const stmt = await db.prepare("SELECT COUNT(*) AS count FROM test");
+                            const row = await stmt.get();
+                            return `${result}:${row.count}`;
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(result, "done:2");
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rollback_on_throw() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+                            let message;
+                            try {
+                                await db.transaction(async (tx) => {
+                                    await tx.exec("INSERT INTO test (name) VALUES ('test')");
+                                    throw new Error("boom");
+                                });
+                            } catch (e) {
+                                message = e.message;
+                            }
+                            const stmt = await db.prepare("SELECT COUNT(*) AS count FROM test");
+                            const row = await stmt.get();
+                            return `${message}:${row.count}`;
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(result, "boom:0");
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_nested() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+                            await db.transaction(async (tx) => {
+                                await tx.exec("INSERT INTO test (name) VALUES ('outer')");
+                                try {
+                                    await tx.transaction(async (nested) => {
+                                        await nested.exec("INSERT INTO test (name) VALUES ('inner')");
+                                        throw new Error("boom");
+                                    });
+                                } catch {}
+                                await tx.transaction(async (nested) => {
+                                    await nested.exec("INSERT INTO test (name) VALUES ('inner2')");
+                                });
+                            });
+                            const stmt = await db.prepare("SELECT name FROM test ORDER BY id");
+                            const rows = await stmt.all();
+                            return rows.map((row) => row.name).join(",");
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(result, "outer,inner2");
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_manual() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+
+                            const tx = await db.begin();
+                            await tx.exec("INSERT INTO test (name) VALUES ('rolled back')");
+                            await tx.rollback();
+
+                            const tx2 = await db.begin();
+                            await tx2.exec("INSERT INTO test (name) VALUES ('committed')");
+                            await tx2.commit();
+
+                            let message;
+                            try {
+                                await tx2.exec("INSERT INTO test (name) VALUES ('late')");
+                            } catch (e) {
+                                message = e.message;
+                            }
+
+                            const stmt = await db.prepare("SELECT name FROM test");
+                            const rows = await stmt.all();
+                            return `${rows.map((row) => row.name).join(",")}:${message}`;
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(result, "committed:Transaction is already finished");
+            })
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_iterator_busy() {
+        test_async_with(|ctx| {
+            Box::pin(async move {
+                ModuleEvaluator::eval_rust::<SqliteModule>(ctx.clone(), "sqlite")
+                    .await
+                    .unwrap();
+
+                let module = ModuleEvaluator::eval_js(
+                    ctx.clone(),
+                    "test",
+                    r#"
+                        import { open } from "sqlite";
+
+                        export async function test() {
+                            const db = await open({ inMemory: true });
+                            await db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+                            await db.exec(`
+                                WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 100)
+                                INSERT INTO test (name) SELECT 'row' || n FROM seq;
+                            `);
+                            let message;
+                            await db.transaction(async (tx) => {
+                                const stmt = await tx.prepare("SELECT name FROM test ORDER BY id");
+                                for await (const row of stmt.iterate()) {
+                                    try {
+                                        await tx.exec(`INSERT INTO test (name) VALUES ('${row.name}2')`);
+                                    } catch (e) {
+                                        message = e.message;
+                                    }
+                                    break;
+                                }
+                                // The connection is released once the iterator is closed
+                                await tx.exec("INSERT INTO test (name) VALUES ('c')");
+                            });
+                            const stmt = await db.prepare("SELECT name FROM test ORDER BY id DESC LIMIT 2");
+                            const rows = await stmt.pluck().all();
+                            return `${message}:${rows.join(",")}`;
+                        }
+                    "#,
+                )
+                .await
+                .catch(&ctx)
+                .unwrap();
+
+                let result = call_test::<String, _>(&ctx, &module, ()).await;
+                assert_eq!(result, "Connection is busy with an open iterator:c,row100");
+            })
+        })
+        .await;
+    }
+}
@@ -1,8 +1,91 @@
-use rquickjs::{Ctx, Exception, IntoJs, Result, String, TypedArray};
+use std::borrow::Cow;
+use std::slice;
+
+use std::ffi::c_int;
+
+use libsqlite3_sys::{
+    SQLITE_BLOB, SQLITE_FLOAT, SQLITE_INTEGER, SQLITE_NULL, SQLITE_TEXT, sqlite3_column_blob,
+    sqlite3_column_bytes, sqlite3_column_double, sqlite3_column_int64, sqlite3_column_text,
+    sqlite3_column_type, sqlite3_stmt, sqlite3_value, sqlite3_value_blob, sqlite3_value_bytes,
+    sqlite3_value_double, sqlite3_value_int64, sqlite3_value_text, sqlite3_value_type,
+};
+use rquickjs::function::Constructor;
+use rquickjs::{BigInt, Ctx, Exception, IntoJs, Result, String, TypedArray};
 use rquickjs_extra_utils::result::ResultExt;
 use sqlx::sqlite::{SqliteColumn, SqliteRow};
 use sqlx::{Column as _, Decode, Row as _, TypeInfo as _, ValueRef};
 
+/// How values read from the database are converted to JS.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadOptions {
+    /// Use the declared type of columns to decode booleans, dates and JSON.
+    pub declared_types: bool,
+    /// Read integers as `BigInt`.
+    pub safe_integers: bool,
+}
+
+/// Largest integer that a JS number can represent exactly (`Number.MAX_SAFE_INTEGER`).
+pub(crate) const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// Converts an integer to JS, as a `BigInt` when `safe_integers` is set.
+/// Otherwise integers that would lose precision as a number throw a `RangeError`.
+pub fn integer_into_js<'js>(
+    ctx: &Ctx<'js>,
+    int: i64,
+    safe_integers: bool,
+) -> Result<rquickjs::Value<'js>> {
+    if safe_integers {
+        return Ok(BigInt::from_i64(ctx.clone(), int)?.into_value());
+    }
+    if !(-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&int) {
+        return Err(Exception::throw_range(
+            ctx,
+            &[
+                "Integer ",
+                &int.to_string(),
+                " cannot be represented as a number without losing precision, use safeIntegers to read it as a BigInt",
+            ]
+            .concat(),
+        ));
+    }
+    int.into_js(ctx)
+}
+
+/// Converts a rowid to JS, as a `BigInt` when `safe_integers` is set or when
+/// it would lose precision as a number. Rowids are read once the statement
+/// has run, so they never throw.
+pub fn rowid_into_js<'js>(
+    ctx: &Ctx<'js>,
+    rowid: i64,
+    safe_integers: bool,
+) -> Result<rquickjs::Value<'js>> {
+    let safe_integers = safe_integers || !(-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&rowid);
+    integer_into_js(ctx, rowid, safe_integers)
+}
+
+/// The declared types of columns that are decoded to richer JS values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclaredType {
+    Boolean,
+    Date,
+    Json,
+    Other,
+}
+
+impl DeclaredType {
+    pub fn parse(declared: Option<&str>) -> Self {
+        let Some(declared) = declared else {
+            return DeclaredType::Other;
+        };
+        match declared.trim().to_ascii_uppercase().as_str() {
+            "BOOLEAN" | "BOOL" => DeclaredType::Boolean,
+            "DATE" | "DATETIME" | "TIMESTAMP" => DeclaredType::Date,
+            "JSON" => DeclaredType::Json,
+            _ => DeclaredType::Other,
+        }
+    }
+}
+
 pub enum Value<'q> {
     Null,
     Integer(i64),
@@ -18,7 +101,7 @@ where
     fn into_js(self, ctx: &Ctx<'js>) -> Result<rquickjs::Value<'js>> {
         match self {
             Value::Null => Ok(rquickjs::Value::new_null(ctx.clone())),
-            Value::Integer(int) => Ok(int.into_js(ctx)?),
+            Value::Integer(int) => integer_into_js(ctx, int, false),
             Value::Real(float) => Ok(float.into_js(ctx)?),
             Value::Text(s) => Ok(String::from_str(ctx.clone(), s)?.into_value()),
             Value::Blob(b) => Ok(TypedArray::<u8>::new_copy(ctx.clone(), b)?.into_value()),
@@ -27,6 +110,33 @@ where
 }
 
 impl<'q> Value<'q> {
+    /// Converts the value to JS using the declared type of its column.
+    /// Values whose storage class does not match the declared type are converted as is.
+    pub fn into_js_typed<'js>(
+        self,
+        ctx: &Ctx<'js>,
+        declared: DeclaredType,
+        safe_integers: bool,
+    ) -> Result<rquickjs::Value<'js>>
+    where
+        'js: 'q,
+    {
+        match (declared, self) {
+            (DeclaredType::Boolean, Value::Integer(int)) => (int != 0).into_js(ctx),
+            (DeclaredType::Date, Value::Text(text)) => {
+                let date: Constructor = ctx.globals().get("Date")?;
+                date.construct((normalize_datetime(text).as_ref(),))
+            }
+            (DeclaredType::Date, Value::Integer(seconds)) => {
+                let date: Constructor = ctx.globals().get("Date")?;
+                date.construct((seconds as f64 * 1000.0,))
+            }
+            (DeclaredType::Json, Value::Text(text)) => ctx.json_parse(text),
+            (_, Value::Integer(int)) => integer_into_js(ctx, int, safe_integers),
+            (_, value) => value.into_js(ctx),
+        }
+    }
+
     pub fn try_read(ctx: &Ctx<'_>, column: &'q SqliteColumn, row: &'q SqliteRow) -> Result<Self> {
         let value = row.try_get_raw(column.ordinal()).or_throw(ctx)?;
 
@@ -47,4 +157,136 @@ impl<'q> Value<'q> {
             )),
         }
     }
+
+    /// Reads a value passed by SQLite to an application-defined function.
+    ///
+    /// # Safety
+    /// `value` must be a valid `sqlite3_value` that is not modified or freed
+    /// for the lifetime `'q`.
+    pub unsafe fn try_from_raw(ctx: &Ctx<'_>, value: *mut sqlite3_value) -> Result<Self> {
+        unsafe {
+            match sqlite3_value_type(value) {
+                SQLITE_NULL => Ok(Value::Null),
+                SQLITE_INTEGER => Ok(Value::Integer(sqlite3_value_int64(value))),
+                SQLITE_FLOAT => Ok(Value::Real(sqlite3_value_double(value))),
+                SQLITE_TEXT => {
+                    // Text must be read before its length, see https://www.sqlite.org/c3ref/value_blob.html
+                    let ptr = sqlite3_value_text(value);
+                    let len = sqlite3_value_bytes(value) as usize;
+                    let bytes = if ptr.is_null() {
+                        &[]
+                    } else {
+                        slice::from_raw_parts(ptr, len)
+                    };
+                    Ok(Value::Text(str::from_utf8(bytes).or_throw(ctx)?))
+                }
+                SQLITE_BLOB => {
+                    let ptr = sqlite3_value_blob(value) as *const u8;
+                    let len = sqlite3_value_bytes(value) as usize;
+                    let bytes = if ptr.is_null() {
+                        &[]
+                    } else {
+                        slice::from_raw_parts(ptr, len)
+                    };
+                    Ok(Value::Blob(bytes))
+                }
+                kind => Err(Exception::throw_message(
+                    ctx,
+                    &["Unsupported type: ", &kind.to_string()].concat(),
+                )),
+            }
+        }
+    }
+
+    /// Reads a column of the current row of a raw statement.
+    ///
+    /// # Safety
+    /// `stmt` must be a valid statement positioned on a row, which is not
+    /// stepped, reset or finalized for the lifetime `'q`.
+    pub unsafe fn try_from_column(
+        ctx: &Ctx<'_>,
+        stmt: *mut sqlite3_stmt,
+        index: c_int,
+    ) -> Result<Self> {
+        unsafe {
+            match sqlite3_column_type(stmt, index) {
+                SQLITE_NULL => Ok(Value::Null),
+                SQLITE_INTEGER => Ok(Value::Integer(sqlite3_column_int64(stmt, index))),
+                SQLITE_FLOAT => Ok(Value::Real(sqlite3_column_double(stmt, index))),
+                SQLITE_TEXT => {
+                    // Text must be read before its length, see https://www.sqlite.org/c3ref/column_blob.html
+                    let ptr = sqlite3_column_text(stmt, index);
+                    let len = sqlite3_column_bytes(stmt, index) as usize;
+                    let bytes = if ptr.is_null() {
+                        &[]
+                    } else {
+                        slice::from_raw_parts(ptr, len)
+                    };
+                    Ok(Value::Text(str::from_utf8(bytes).or_throw(ctx)?))
+                }
+                SQLITE_BLOB => {
+                    let ptr = sqlite3_column_blob(stmt, index) as *const u8;
+                    let len = sqlite3_column_bytes(stmt, index) as usize;
+                    let bytes = if ptr.is_null() {
+                        &[]
+                    } else {
+                        slice::from_raw_parts(ptr, len)
+                    };
+                    Ok(Value::Blob(bytes))
+                }
+                kind => Err(Exception::throw_message(
+                    ctx,
+                    &["Unsupported type: ", &kind.to_string()].concat(),
+                )),
+            }
+        }
+    }
+}
+
+/// SQLite date functions produce `YYYY-MM-DD HH:MM:SS` in UTC,
+/// which must be made ISO-8601 for `Date` to parse it as UTC.
+fn normalize_datetime(text: &str) -> Cow<'_, str> {
+    let Some(time) = text.get(11..) else {
+        return Cow::Borrowed(text);
+    };
+    if text.as_bytes()[10] != b' ' {
+        return Cow::Borrowed(text);
+    }
+    let zoned = time.ends_with('Z') || time.contains('+') || time.contains('-');
+    let mut iso = [&text[..10], "T", time].concat();
+    if !zoned {
+        iso.push('Z');
+    }
+    Cow::Owned(iso)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeclaredType, normalize_datetime};
+
+    #[test]
+    fn test_declared_type() {
+        assert_eq!(DeclaredType::parse(Some("boolean")), DeclaredType::Boolean);
+        assert_eq!(DeclaredType::parse(Some("DATETIME")), DeclaredType::Date);
+        assert_eq!(DeclaredType::parse(Some("Json")), DeclaredType::Json);
+        assert_eq!(DeclaredType::parse(Some("TEXT")), DeclaredType::Other);
+        assert_eq!(DeclaredType::parse(None), DeclaredType::Other);
+    }
+
+    #[test]
+    fn test_normalize_datetime() {
+        assert_eq!(normalize_datetime("2024-01-02"), "2024-01-02");
+        assert_eq!(
+            normalize_datetime("2024-01-02 03:04:05"),
+            "2024-01-02T03:04:05Z"
+        );
+        assert_eq!(
+            normalize_datetime("2024-01-02 03:04:05+02:00"),
+            "2024-01-02T03:04:05+02:00"
+        );
+        assert_eq!(
+            normalize_datetime("2024-01-02T03:04:05.000Z"),
+            "2024-01-02T03:04:05.000Z"
+        );
+    }
 }
@@ -1,25 +1,292 @@
 declare module "sqlite" {
-  export type Parameter = null | number | bigint | string | Uint8Array;
+  /**
+   * A value bound to a statement. Booleans are bound as `0` or `1`, dates as ISO-8601 text
+   * and plain objects or arrays as JSON text.
+   */
+  export type Parameter =
+    | null
+    | number
+    | bigint
+    | string
+    | boolean
+    | Date
+    | Uint8Array
+    | unknown[]
+    | { [key: string]: unknown };
+  /**
+   * Values for named parameters (`:name`, `@name` and `$name`), numbered ones (`?NNN`) can be set too.
+   * Keys can be written with or without their prefix. Statements without named parameters bind
+   * a trailing object as JSON like any other. Other values bind the anonymous parameters in order,
+   * a `RangeError` is thrown when there are too many or too few of them.
+   */
+  export type NamedParameters = Record<string, Parameter>;
+  export type Parameters = Parameter[] | [...Parameter[], NamedParameters];
+  /**
+   * The part of the `AbortSignal` interface used to interrupt queries.
+   */
+  export interface AbortSignalLike {
+    readonly aborted: boolean;
+    readonly reason?: unknown;
+    addEventListener(type: "abort", listener: () => void): void;
+    removeEventListener(type: "abort", listener: () => void): void;
+  }
+  /**
+   * Limits on the execution of a query. An interrupted query rejects with an `Error` named
+   * `AbortError` with code `ABORT_ERR`, whose `cause` is the reason of the signal.
+   */
+  export type QueryOptions = {
+    /**
+     * Interrupts the query when aborted.
+     */
+    signal?: AbortSignalLike | undefined;
+    /**
+     * Interrupts the query after this many milliseconds.
+     */
+    timeout?: number | undefined;
+  };
+  /**
+   * Parameters optionally followed by query options. A trailing object whose keys are all `signal`
+   * or `timeout` is read as options once the values before it bind every parameter, unless one of
+   * its keys names a parameter of the statement.
+   */
+  export type ParametersWithOptions = Parameters | [...Parameters, QueryOptions];
   export type Result = {
     changes: number;
-    lastInsertRowid: number;
+    /**
+     * A `bigint` when safe integers are enabled or when it does not fit in a number.
+     */
+    lastInsertRowid: number | bigint;
+  };
+
+  export type FunctionOptions = {
+    /**
+     * If true, the function always returns the same result for the same arguments,
+     * which allows SQLite to use it in indexes and to optimize calls.
+     * @default false
+     */
+    deterministic?: boolean | undefined;
+    /**
+     * If true, the function accepts any number of arguments.
+     * Otherwise the number of arguments is the `length` of the callback.
+     * @default false
+     */
+    varargs?: boolean | undefined;
+    /**
+     * If true, the function can only be called from top-level SQL,
+     * not from views, triggers or schema structures.
+     * @default false
+     */
+    directOnly?: boolean | undefined;
+  };
+
+  export type AggregateOptions<T = any> = FunctionOptions & {
+    /**
+     * The initial value of the accumulator, or a function returning it for each group.
+     * @default null
+     */
+    start?: T | (() => T) | undefined;
+    /**
+     * Called for each row with the accumulator and the arguments of the function.
+     * Returns the new accumulator, or `undefined` to keep the current one.
+     */
+    step: (accumulator: T, ...args: any[]) => T | void;
+    /**
+     * Removes a row from the accumulator. Providing it creates a
+     * {@link https://www.sqlite.org/windowfunctions.html window function}.
+     */
+    inverse?: ((accumulator: T, ...args: any[]) => T | void) | undefined;
+    /**
+     * Computes the result from the accumulator. The accumulator is the result if omitted.
+     */
+    result?: ((accumulator: T) => Parameter) | undefined;
+  };
+
+  export type ColumnDefinition = {
+    /**
+     * The name of the column in the result set.
+     */
+    name: string;
+    /**
+     * The name of the table column the result column originates from, `null` for expressions.
+     */
+    column: string | null;
+    /**
+     * The name of the table the result column originates from, `null` for expressions.
+     */
+    table: string | null;
+    /**
+     * The name of the database the result column originates from, `null` for expressions.
+     */
+    database: string | null;
+    /**
+     * The declared type of the column, `null` for expressions.
+     */
+    type: string | null;
+  };
+
+  export type BackupStatus = {
+    totalPages: number;
+    remainingPages: number;
+  };
+
+  export type BackupOptions = {
+    /**
+     * Number of pages copied in each step. Other connections can write between steps.
+     * A negative value copies all pages at once.
+     * @default 100
+     */
+    pagesPerStep?: number | undefined;
+    /**
+     * Called after each step until the backup completes.
+     */
+    progress?: ((status: BackupStatus) => void) | undefined;
+  };
+
+  export type BlobOptions = {
+    /**
+     * Opens the blob for reading only.
+     * @default false
+     */
+    readOnly?: boolean | undefined;
+    /**
+     * The name of the attached database containing the table.
+     * @default "main"
+     */
+    database?: string | undefined;
+  };
+
+  export type SessionOptions = {
+    /**
+     * The tables whose changes are recorded, every table when omitted.
+     * Only tables with a primary key can be recorded.
+     */
+    tables?: string[] | undefined;
+    /**
+     * The attached database whose changes are recorded.
+     * @default "main"
+     */
+    database?: string | undefined;
+  };
+
+  export type ConflictType = "data" | "notFound" | "conflict" | "constraint" | "foreignKey";
+
+  export type ApplyChangesetOptions = {
+    /**
+     * Called with the name of each table of the changeset, its changes are skipped when it returns `false`.
+     */
+    filter?: ((table: string) => boolean) | undefined;
+    /**
+     * Called when a change conflicts with the database, see
+     * {@link https://www.sqlite.org/session/sqlite3changeset_apply.html sqlite3changeset_apply}.
+     * Only `data` and `conflict` conflicts can be replaced. `table` is undefined for
+     * `foreignKey` conflicts. The changeset is aborted when omitted.
+     */
+    onConflict?:
+      | ((type: ConflictType, table: string | undefined) => "omit" | "replace" | "abort")
+      | undefined;
+  };
+
+  /**
+   * Options of {@link open}. Unknown options, values of the wrong type and conflicting
+   * options throw a `TypeError` naming the option.
+   */
+  export type PragmaValue = number | bigint | string | boolean;
+
+  export type PragmaOptions = {
+    /**
+     * Returns the first column of the first row instead of all rows.
+     */
+    simple?: boolean | undefined;
+  };
+
+  /**
+   * SQL to execute, or a function called with the transaction of the migration.
+   */
+  export type MigrationStep = string | ((tx: Transaction) => void | Promise<void>);
+
+  export type Migration = {
+    /**
+     * A positive integer, increasing with each migration.
+     */
+    id: number;
+    up: MigrationStep;
+    /**
+     * Reverts `up`, required to migrate to an older version.
+     */
+    down?: MigrationStep | undefined;
+  };
+
+  export type MigrateOptions = {
+    /**
+     * The id of the migration to migrate to, or `0` to revert all migrations.
+     * Defaults to the last migration.
+     */
+    to?: number | undefined;
+    /**
+     * The table recording the applied migrations.
+     * Defaults to `"_migrations"`.
+     */
+    table?: string | undefined;
+  };
+
+  export type MigrateResult = {
+    from: number;
+    to: number;
+    /**
+     * The ids of the migrations applied or reverted, in the order they ran.
+     */
+    migrations: number[];
   };
 
   export type OpenOptions = {
     /**
      * The filename of the database. If the file does not exist, a new one will be created.
+     * Cannot be used with `inMemory` or `uri`.
      */
     filename?: string | undefined;
     /**
      * If true, the database will be opened in-memory.
+     * @default true when neither `filename` nor `uri` is set
+     */
+    inMemory?: boolean | undefined;
+    /**
+     * A {@link https://www.sqlite.org/uri.html `file:` URI} locating the database, which replaces
+     * `filename` and `inMemory`. The `mode`, `cache`, `immutable` and `vfs` query parameters are supported,
+     * other parameters are rejected with a `TypeError`.
+     *
+     * @example "file:data.db?mode=ro"
+     */
+    uri?: string | undefined;
+    /**
+     * If true, the database is opened read-only and cannot be modified.
      * @default false
      */
-    in_memory?: boolean | undefined;
+    readOnly?: boolean | undefined;
     /**
-     * If true, the database will use the WAL mode.
+     * If false, opening a database file that does not exist fails instead of creating it.
+     * @default true
+     */
+    create?: boolean | undefined;
+    /**
+     * If true, the database file is assumed to never change, even by other processes,
+     * so it is read without any locking. Implies `readOnly`.
+     * @default false
+     */
+    immutable?: boolean | undefined;
+    /**
+     * If true, the database will use the WAL mode. Ignored by read-only databases.
      * @default true
      */
     wal?: boolean | undefined;
+    /**
+     * The {@link https://www.sqlite.org/pragma.html#pragma_journal_mode journal mode}, which takes
+     * precedence over `wal`.
+     */
+    journalMode?: "delete" | "truncate" | "persist" | "memory" | "wal" | "off" | undefined;
+    /**
+     * The {@link https://www.sqlite.org/pragma.html#pragma_synchronous synchronous} setting.
+     */
+    synchronous?: "off" | "normal" | "full" | "extra" | undefined;
     /**
      * Set the SQlite page size.
      * @default 4096
@@ -27,8 +294,13 @@ declare module "sqlite" {
     pageSize?: number | undefined;
     /**
      * Enable foreign key constraints.
+     * @default true
      */
     foreignKeys?: boolean | undefined;
+    /**
+     * Node.js name of {@link OpenOptions.foreignKeys}.
+     */
+    enableForeignKeyConstraints?: boolean | undefined;
     /**
      * Maximum number of connections to the database.
      * @default 5
@@ -44,17 +316,54 @@ declare module "sqlite" {
      * @default infinity
      */
     idleTimeout?: number | undefined;
+    /**
+     * {@link OpenOptions.idleTimeout} in milliseconds.
+     */
+    idleTimeoutMs?: number | undefined;
     /**
      * Maximum amount of time (in seconds) that a connection is allowed to exist before it is closed.
      * Set to `null` to disable.
      * @default 3600
      */
-    maxLifetime?: number | undefined;
+    maxLifetime?: number | null | undefined;
+    /**
+     * {@link OpenOptions.maxLifetime} in milliseconds.
+     */
+    maxLifetimeMs?: number | null | undefined;
     /**
-     * Time (in milliseconds) to wait for the database to be unlocked before throwing an error.
+     * Time (in milliseconds, at most 2147483647) to wait for the database to be unlocked before
+     * throwing an error.
      * @default 5000
      */
     busyTimeout?: number | undefined;
+    /**
+     * Node.js name of {@link OpenOptions.busyTimeout}, in milliseconds.
+     */
+    timeout?: number | undefined;
+    /**
+     * If true, the declared type of columns is used to decode values:
+     * `BOOLEAN` columns are read as booleans, `DATE`, `DATETIME` and `TIMESTAMP` columns as `Date`
+     * (from ISO-8601 text or Unix time in seconds) and `JSON` columns are parsed.
+     * @default false
+     */
+    declaredTypes?: boolean | undefined;
+    /**
+     * If true, integers are read as `BigInt`. Otherwise reading an integer that cannot be
+     * represented exactly as a number throws a `RangeError`.
+     * @default false
+     */
+    safeIntegers?: boolean | undefined;
+    /**
+     * Allows loading extensions with {@link Database.loadExtension}.
+     * Requires the `sqlite-extensions` feature.
+     * @default false
+     */
+    allowExtension?: boolean | undefined;
+    /**
+     * A serialized database, as returned by {@link Database.serialize}, to load into the
+     * in-memory database.
+     */
+    data?: Uint8Array | undefined;
   };
 
   /**
@@ -73,11 +382,326 @@ declare module "sqlite" {
     /**
      * This method allows one or more SQL statements to be executed without returning any results.
      */
-    exec(sql: string): Promise<void>;
+    exec(sql: string, options?: QueryOptions): Promise<void>;
+    /**
+     * Runs a {@link https://www.sqlite.org/pragma.html pragma} and returns its rows.
+     * Pragmas setting the state of a connection, like `cache_size` or `foreign_keys`,
+     * are applied to every connection of the pool. Setting other pragmas not known to
+     * act on the database file logs a warning, as they only run on one connection.
+     *
+     * @example
+     * ```ts
+     * const mode = await db.pragma("journal_mode", { simple: true });
+     * await db.pragma("cache_size", -2000);
+     * const columns = await db.pragma("table_info", "users");
+     * ```
+     */
+    pragma<T = object>(name: string, options?: PragmaOptions & { simple?: false }): Promise<T[]>;
+    pragma<T = object>(
+      name: string,
+      value: PragmaValue,
+      options?: PragmaOptions & { simple?: false },
+    ): Promise<T[]>;
+    pragma<T = unknown>(name: string, options: PragmaOptions & { simple: true }): Promise<T | undefined>;
+    pragma<T = unknown>(
+      name: string,
+      value: PragmaValue,
+      options: PragmaOptions & { simple: true },
+    ): Promise<T | undefined>;
+    /**
+     * Applies the pending migrations in a single `BEGIN IMMEDIATE` transaction, or reverts the
+     * applied ones down to `options.to`. Nothing is changed if a migration fails.
+     *
+     * The version of the database is its `user_version`, which is the id of the last applied
+     * migration. Applied ids are also recorded in a table, and migrating is refused when the
+     * history does not match `migrations`, like a migration added before an applied one.
+     *
+     * @example
+     * ```ts
+     * await db.migrate([
+     *   { id: 1, up: "CREATE TABLE users (id INTEGER PRIMARY KEY)", down: "DROP TABLE users" },
+     *   { id: 2, up: async (tx) => { await tx.exec("ALTER TABLE users ADD COLUMN name TEXT"); } },
+     * ]);
+     * ```
+     */
+    migrate(migrations: Migration[], options?: MigrateOptions): Promise<MigrateResult>;
     /**
      * Compiles a SQL statement into a {@link https://www.sqlite.org/c3ref/stmt.html prepared statement}.
      */
     prepare(sql: string): Statement;
+    /**
+     * Begins a transaction on a dedicated connection of the pool.
+     * The connection stays pinned until the transaction is committed or rolled back.
+     */
+    begin(): Promise<Transaction>;
+    /**
+     * Runs `callback` inside a transaction. The transaction is committed when the callback
+     * resolves and rolled back when it throws.
+     *
+     * @example
+     * ```ts
+     * await db.transaction(async (tx) => {
+     *   await tx.exec("INSERT INTO test (name) VALUES ('foo');");
+     *   await tx.exec("INSERT INTO test (name) VALUES ('bar');");
+     * });
+     * ```
+     */
+    transaction<T>(callback: (tx: Transaction) => T | Promise<T>): Promise<T>;
+    /**
+     * Registers a JavaScript function that can be called from SQL.
+     * The function is registered on every connection of the pool and must be synchronous.
+     * Errors thrown by the function are reported as SQL errors.
+     *
+     * @example
+     * ```ts
+     * await db.function("normalize", { deterministic: true }, (s) => s.trim().toLowerCase());
+     * await db.exec("SELECT normalize(name) FROM test;");
+     * ```
+     */
+    function(name: string, callback: (...args: any[]) => Parameter): Promise<void>;
+    function(
+      name: string,
+      options: FunctionOptions,
+      callback: (...args: any[]) => Parameter,
+    ): Promise<void>;
+    /**
+     * Registers a JavaScript aggregate function that can be called from SQL.
+     * The function is a window function when `inverse` is provided.
+     * Errors thrown by the callbacks abort the query.
+     *
+     * @example
+     * ```ts
+     * await db.aggregate("product", { start: 1, step: (total, value) => total * value });
+     * await db.exec("SELECT product(value) FROM test;");
+     * ```
+     */
+    aggregate<T>(name: string, options: AggregateOptions<T>): Promise<void>;
+    /**
+     * Registers a collation comparing texts with a JavaScript function, usable with `COLLATE`
+     * in queries, indexes and column definitions.
+     * The collation is registered on every connection of the pool and `compare` must be synchronous.
+     * An error thrown by `compare` interrupts the query, which rejects with that error.
+     * Queries read with an iterator reject with an interrupt error instead and the error is logged.
+     *
+     * @example
+     * ```ts
+     * const collator = new Intl.Collator("de");
+     * await db.collation("german", collator.compare);
+     * await db.exec("SELECT name FROM test ORDER BY name COLLATE german;");
+     * ```
+     *
+     * @param compare Returns a negative number, zero or a positive number when `a` sorts before, equal to or after `b`.
+     */
+    collation(name: string, compare: (a: string, b: string) => number): Promise<void>;
+    /**
+     * Listens to the rows inserted, updated or deleted by any connection of the pool.
+     * Listeners are called asynchronously after the change, in registration order.
+     * Changes to `WITHOUT ROWID` tables are not reported. The rowid is a `bigint` when safe integers
+     * are enabled or when it does not fit in a number.
+     *
+     * @returns A function removing the listener.
+     *
+     * @example
+     * ```ts
+     * const off = await db.onUpdate((operation, database, table, rowid) => {
+     *   console.log(operation, table, rowid);
+     * });
+     * ```
+     */
+    onUpdate(
+      listener: (
+        operation: "insert" | "update" | "delete",
+        database: string,
+        table: string,
+        rowid: number | bigint,
+      ) => void,
+    ): Promise<() => void>;
+    /**
+     * Listens to the transactions committed by any connection of the pool.
+     * Listeners are called asynchronously and cannot abort the commit.
+     *
+     * @returns A function removing the listener.
+     */
+    onCommit(listener: () => void): Promise<() => void>;
+    /**
+     * Listens to the transactions rolled back by any connection of the pool.
+     * Listeners are called asynchronously.
+     *
+     * @returns A function removing the listener.
+     */
+    onRollback(listener: () => void): Promise<() => void>;
+    /**
+     * Copies the database to the file at `destination` using the
+     * {@link https://www.sqlite.org/backup.html online backup API}.
+     * The file is overwritten if it exists. In-memory databases can be backed up too, read-only ones
+     * are refused with `SQLITE_READONLY` since the backup writes a file. Steps that find either
+     * database locked are retried until the busy timeout of the database expires.
+     *
+     * @example
+     * ```ts
+     * await db.backup("path/to/backup.sqlite", {
+     *   progress: ({ totalPages, remainingPages }) => console.log(remainingPages, totalPages),
+     * });
+     * ```
+     */
+    backup(destination: string, options?: BackupOptions): Promise<BackupStatus>;
+    /**
+     * Returns the content of the database as a `Uint8Array`, in the SQLite file format.
+     */
+    serialize(): Promise<Uint8Array>;
+    /**
+     * Replaces the content of the database with a serialized database,
+     * as returned by {@link Database.serialize}.
+     */
+    deserialize(data: Uint8Array): Promise<void>;
+    /**
+     * Opens a handle reading and writing a blob in place, in chunks, instead of copying the whole value.
+     * The handle pins a connection of the pool until it is closed.
+     *
+     * @example
+     * ```ts
+     * await db.exec("INSERT INTO files (id, data) VALUES (1, zeroblob(1048576))");
+     * const blob = await db.openBlob("files", "data", 1);
+     * await blob.write(0, chunk);
+     * await blob.close();
+     * ```
+     *
+     * @param rowid The rowid of the row containing the blob.
+     */
+    openBlob(table: string, column: string, rowid: number | bigint, options?: BlobOptions): Promise<Blob>;
+    /**
+     * Starts a {@link https://www.sqlite.org/sessionintro.html session} recording the changes
+     * made through it, on a dedicated connection of the pool.
+     * Requires the `session` feature of the module.
+     *
+     * @example
+     * ```ts
+     * const session = await db.session({ tables: ["todos"] });
+     * await session.exec("INSERT INTO todos (title) VALUES ('sync')");
+     * const changeset = await session.changeset();
+     * await session.close();
+     * await other.applyChangeset(changeset, { onConflict: () => "replace" });
+     * ```
+     */
+    session(options?: SessionOptions): Promise<Session>;
+    /**
+     * Applies a changeset or patchset in a transaction.
+     * Requires the `session` feature of the module.
+     *
+     * @returns `false` if the changeset was aborted by a conflict.
+     */
+    applyChangeset(changeset: Uint8Array, options?: ApplyChangesetOptions): Promise<boolean>;
+    /**
+     * Loads a {@link https://www.sqlite.org/loadext.html loadable extension} on every connection of the pool.
+     * Requires the `sqlite-extensions` feature and the `allowExtension` open option.
+     * The `load_extension()` SQL function stays disabled.
+     *
+     * @param path The path of the shared library, with or without its file extension.
+     * @param entryPoint The name of the initialization function, derived from the filename by default.
+     */
+    loadExtension(path: string, entryPoint?: string): Promise<void>;
+    /**
+     * Toggles reading integers as `BigInt` for the statements and transactions created afterwards.
+     * Statements can override it with {@link Statement.safeIntegers}.
+     *
+     * @param toggle Whether to read integers as `BigInt`.
+     * @default true
+     */
+    safeIntegers(toggle?: boolean): this;
+  }
+
+  /**
+   * A transaction pinned to a single connection.
+   * Nested transactions are implemented with {@link https://www.sqlite.org/lang_savepoint.html savepoints}.
+   * This class cannot be instantiated via its constructor.
+   * Instead, instances are created via the database.begin() or database.transaction() methods.
+   */
+  export class Transaction {
+    /**
+     * Executes one or more SQL statements inside the transaction without returning any results.
+     */
+    exec(sql: string, options?: QueryOptions): Promise<void>;
+    /**
+     * Compiles a SQL statement that will be executed inside the transaction.
+     */
+    prepare(sql: string): Promise<Statement>;
+    /**
+     * Begins a nested transaction using a savepoint.
+     */
+    begin(): Promise<Transaction>;
+    /**
+     * Runs `callback` inside a nested transaction using a savepoint.
+     * The savepoint is released when the callback resolves and rolled back when it throws.
+     */
+    transaction<T>(callback: (tx: Transaction) => T | Promise<T>): Promise<T>;
+    /**
+     * Commits the transaction, or releases the savepoint of a nested transaction.
+     */
+    commit(): Promise<void>;
+    /**
+     * Rolls back the transaction, or rolls back to the savepoint of a nested transaction.
+     */
+    rollback(): Promise<void>;
+  }
+
+  /**
+   * A session recording the changes made through its connection, which stays pinned until
+   * the session is closed. This class cannot be instantiated via its constructor.
+   * Instead, instances are created via the database.session() method.
+   */
+  export class Session {
+    /**
+     * Executes one or more SQL statements on the connection of the session.
+     */
+    exec(sql: string, options?: QueryOptions): Promise<void>;
+    /**
+     * Compiles a SQL statement that will be executed on the connection of the session.
+     */
+    prepare(sql: string): Promise<Statement>;
+    /**
+     * Returns the changes recorded so far as a changeset.
+     */
+    changeset(): Promise<Uint8Array>;
+    /**
+     * Returns the changes recorded so far as a patchset, which is smaller than a changeset
+     * but omits the original values of updated and deleted rows.
+     */
+    patchset(): Promise<Uint8Array>;
+    /**
+     * Stops recording and releases the connection of the session.
+     */
+    close(): Promise<void>;
+  }
+
+  /**
+   * A handle reading and writing a blob in place, created by {@link Database.openBlob}.
+   * The handle keeps a transaction open on its connection: writes are committed and locks are
+   * released when it is closed. This class cannot be instantiated via its constructor.
+   */
+  export class Blob {
+    private constructor();
+    /**
+     * The size of the blob in bytes, which cannot be changed through the handle.
+     */
+    readonly size: number;
+    /**
+     * Reads `length` bytes from `offset`.
+     *
+     * @param offset @default 0
+     * @param length @default the bytes until the end of the blob
+     * @throws {RangeError} If the range is outside of the blob.
+     */
+    read(offset?: number, length?: number): Promise<Uint8Array>;
+    /**
+     * Writes `bytes` at `offset`.
+     *
+     * @throws {RangeError} If the bytes do not fit in the blob.
+     */
+    write(offset: number, bytes: Uint8Array): Promise<void>;
+    /**
+     * Closes the blob, committing its writes, and releases its connection.
+     */
+    close(): Promise<void>;
   }
 
   /**
@@ -85,31 +709,269 @@ declare module "sqlite" {
    * Instead, instances are created via the database.prepare() method.
    */
   export class Statement {
+    /**
+     * The SQL used to prepare the statement.
+     */
+    readonly sql: string;
+    /**
+     * The number of parameters of the statement.
+     */
+    readonly paramCount: number;
+    /**
+     * Returns the metadata of the columns of the results of the statement.
+     */
+    columns(): ColumnDefinition[];
+    /**
+     * Returns the SQL of the statement with its parameters replaced by the values in `params`.
+     *
+     * @param params The values to bind to the prepared statement. When the statement has named parameters, a trailing object binds them by name.
+     */
+    expandedSql(...params: Parameters): Promise<string | null>;
     /**
      * This method executes a prepared statement and returns all results as an array of objects.
      * If the prepared statement does not return any results, this method returns an empty array.
      * The prepared statement {@link https://www.sqlite.org/c3ref/bind_blob.html parameters are bound} using the values in `params`.
      *
-     * @param params The values to bind to the prepared statement. Named parameters are not supported.
+     * @param params The values to bind to the prepared statement, optionally followed by query options. When the statement has named parameters, a trailing object binds them by name.
      */
-    all<T extends object = object>(...params: Parameter[]): Promise<T[]>;
+    all<T extends object = object>(...params: ParametersWithOptions): Promise<T[]>;
     /**
      * This method executes a prepared statement and returns the first result as an object.
      * If the prepared statement does not return any results, this method returns undefined.
      * The prepared statement {@link https://www.sqlite.org/c3ref/bind_blob.html parameters are bound} using the values in params.
      *
-     * @param params The values to bind to the prepared statement. Named parameters are not supported.
+     * @param params The values to bind to the prepared statement, optionally followed by query options. When the statement has named parameters, a trailing object binds them by name.
      */
-    get<T extends object = object>(
-      ...params: Parameter[]
-    ): Promise<T | undefined>;
+    get<T extends object = object>(...params: ParametersWithOptions): Promise<T | undefined>;
     /**
      * This method executes a prepared statement and returns an object summarizing the resulting changes.
      * The prepared statement {@link https://www.sqlite.org/c3ref/bind_blob.html parameters are bound} using the values in params.
      *
-     * @param params The values to bind to the prepared statement. Named parameters are not supported.
+     * @param params The values to bind to the prepared statement, optionally followed by query options. When the statement has named parameters, a trailing object binds them by name.
+     */
+    run(...params: ParametersWithOptions): Promise<Result>;
+    /**
+     * Executes the statement once for each item of `rows` in a single transaction on one connection,
+     * which is nested in a savepoint when the statement belongs to a transaction.
+     * If any execution fails, none of the changes are kept.
+     *
+     * @example
+     * ```ts
+     * const insert = await db.prepare("INSERT INTO test (name) VALUES (?)");
+     * await insert.runMany([["a"], ["b"]]);
+     * ```
+     *
+     * @param rows The parameters of each execution. Items that are not arrays are bound as a single parameter,
+     * like an object of named parameters.
+     * @param options Limits on the execution of the whole batch.
+     * @returns The total number of changes and the rowid inserted by the last execution.
+     */
+    runMany(rows: Iterable<Parameters | Parameter>, options?: QueryOptions): Promise<Result>;
+    /**
+     * This method executes a prepared statement and returns an async iterator over the results.
+     * Rows are streamed from the database as they are consumed instead of being loaded all at once.
+     * The connection is released when the iteration completes or is stopped early with `break` or `return()`.
+     * Statements of a transaction or session share its connection, so their other queries throw
+     * while the iteration is in progress.
+     *
+     * @example
+     * ```ts
+     * for await (const row of stmt.iterate()) {
+     *   console.log(row);
+     * }
+     * ```
+     *
+     * @param params The values to bind to the prepared statement. When the statement has named parameters, a trailing object binds them by name.
+     */
+    iterate<T extends object = object>(...params: Parameters): AsyncIterableIterator<T>;
+    /**
+     * Toggles reading integers as `BigInt` for this statement.
+     * Otherwise reading an integer that cannot be represented exactly as a number throws a `RangeError`.
+     *
+     * @param toggle Whether to read integers as `BigInt`.
+     * @default true
+     */
+    safeIntegers(toggle?: boolean): this;
+    /**
+     * Toggles returning rows as arrays of values in column order instead of objects.
+     * Columns with duplicate names are all kept in this mode.
+     * Disables {@link Statement.pluck}.
+     *
+     * @param toggle Whether to return rows as arrays.
+     * @default true
+     */
+    raw(toggle?: boolean): this;
+    /**
+     * Toggles returning only the value of the first column instead of the whole row.
+     * Disables {@link Statement.raw}.
+     *
+     * @param toggle Whether to return only the first column.
+     * @default true
+     */
+    pluck(toggle?: boolean): this;
+  }
+
+  export type DatabaseSyncOptions = {
+    /**
+     * Opens the database when constructed, otherwise {@link DatabaseSync.open} must be called.
+     * @default true
+     */
+    open?: boolean | undefined;
+    /**
+     * Opens the database in read-only mode.
+     * @default false
+     */
+    readOnly?: boolean | undefined;
+    /**
+     * Enforces foreign key constraints.
+     * @default true
+     */
+    enableForeignKeyConstraints?: boolean | undefined;
+    /**
+     * Accepts double-quoted string literals, which are otherwise identifiers.
+     * @default false
+     */
+    enableDoubleQuotedStringLiterals?: boolean | undefined;
+    /**
+     * The {@link https://www.sqlite.org/c3ref/busy_timeout.html busy timeout} in milliseconds.
+     * @default 0
+     */
+    timeout?: number | undefined;
+  };
+
+  /**
+   * Parameters of a {@link StatementSync}, named parameters come first as in `node:sqlite`.
+   */
+  export type SyncParameters = Parameter[] | [NamedParameters, ...Parameter[]];
+
+  /**
+   * A database with a synchronous API compatible with `node:sqlite`, which does not need an
+   * async runtime. It uses a single connection and every call blocks until SQLite is done.
+   *
+   * @example
+   * ```ts
+   * const db = new DatabaseSync(":memory:");
+   * db.exec("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)");
+   * db.prepare("INSERT INTO test (name) VALUES (?)").run("test");
+   * ```
+   */
+  export class DatabaseSync {
+    /**
+     * @param path The filename of the database, or `:memory:` for an in-memory database.
+     */
+    constructor(path: string, options?: DatabaseSyncOptions);
+    /**
+     * Whether the database is open.
+     */
+    readonly isOpen: boolean;
+    /**
+     * Whether a transaction is active. Throws if the database is not open.
+     */
+    readonly isTransaction: boolean;
+    /**
+     * Opens the database, when it was created with `open: false` or closed.
+     */
+    open(): void;
+    /**
+     * Closes the database, its statements can no longer be used.
+     */
+    close(): void;
+    /**
+     * Returns the filename of the database attached as `name`, or `null` for in-memory databases.
+     *
+     * @param name The name of the attached database.
+     * @default "main"
+     */
+    location(name?: string): string | null;
+    /**
+     * Executes one or more SQL statements without returning any results.
+     */
+    exec(sql: string): void;
+    /**
+     * Compiles the first statement of `sql`.
+     */
+    prepare(sql: string): StatementSync;
+  }
+
+  /**
+   * A prepared statement of a {@link DatabaseSync}. This class cannot be instantiated via its constructor.
+   */
+  export class StatementSync {
+    private constructor();
+    /**
+     * The SQL used to prepare the statement.
+     */
+    readonly sourceSQL: string;
+    /**
+     * The SQL of the statement with the parameters of its last execution expanded.
+     */
+    readonly expandedSQL: string | null;
+    /**
+     * Toggles reading integers as `BigInt`, including `changes` and `lastInsertRowid`.
+     * Otherwise reading an integer that cannot be represented exactly as a number throws a `RangeError`.
+     *
+     * @default false
+     */
+    setReadBigInts(enabled: boolean): void;
+    /**
+     * Toggles binding named parameters without their prefix, like `{ name }` for `$name`.
+     *
+     * @default true
+     */
+    setAllowBareNamedParameters(enabled: boolean): void;
+    /**
+     * Executes the statement and returns all results as an array of objects.
+     */
+    all<T extends object = object>(...params: SyncParameters): T[];
+    /**
+     * Executes the statement and returns the first result, or undefined if there is none.
+     */
+    get<T extends object = object>(...params: SyncParameters): T | undefined;
+    /**
+     * Executes the statement and returns an object summarizing the resulting changes.
+     */
+    run(...params: SyncParameters): Result;
+    /**
+     * Executes the statement and returns an iterator stepping it as rows are consumed.
+     */
+    iterate<T extends object = object>(...params: SyncParameters): IterableIterator<T>;
+  }
+
+  /**
+   * An error reported by SQLite.
+   *
+   * Cannot be constructed from JS, it is only exposed for `instanceof` checks.
+   *
+   * @example
+   * ```ts
+   * try {
+   *   await db.exec("INSERT INTO test (id) VALUES (1);");
+   * } catch (e) {
+   *   if (e instanceof SqliteError && e.code === "SQLITE_CONSTRAINT_PRIMARYKEY") {
+   *     // ...
+   *   }
+   * }
+   * ```
+   */
+  export class SqliteError extends Error {
+    private constructor();
+    readonly name: "SqliteError";
+    /**
+     * The name of the extended result code, like `SQLITE_CONSTRAINT_UNIQUE` or `SQLITE_BUSY`.
+     */
+    readonly code: string;
+    /**
+     * The primary result code, like `19` for `SQLITE_CONSTRAINT`.
+     */
+    readonly errcode: number;
+    /**
+     * The extended result code, like `2067` for `SQLITE_CONSTRAINT_UNIQUE`.
+     */
+    readonly extendedCode: number;
+    /**
+     * The SQL that failed, when known.
      */
-    run(...params: Parameter[]): Promise<Result>;
+    readonly sql: string | undefined;
   }
 
   /**